@@ -0,0 +1,363 @@
+use std::fmt;
+
+use crate::{choice_of, Router};
+
+/// A trace of how `Router::explain` matched a path against every route.
+#[derive(Clone, Debug)]
+pub struct Explanation {
+    path: String,
+    candidates: Vec<Candidate>,
+}
+
+impl Explanation {
+    /// Get the path that was explained.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Get every route of the router.
+    ///
+    /// Matching routes come first, in priority order. The routes that did not
+    /// match follow, ordered by pattern.
+    pub fn candidates(&self) -> &[Candidate] {
+        &self.candidates
+    }
+
+    /// Get the route that `recognize` picks, if any: the highest-ranked
+    /// matching route with a handler for any method.
+    pub fn winner(&self) -> Option<&Candidate> {
+        self.candidates
+            .iter()
+            .take_while(|candidate| candidate.outcome.is_match())
+            .find(|candidate| candidate.handles_any)
+    }
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "path: {}", self.path)?;
+        for candidate in &self.candidates {
+            match candidate.outcome {
+                Outcome::Matched { rank } => {
+                    write!(f, "  {}. {} matched ", rank + 1, candidate.pattern)?;
+                    if !candidate.handles_any {
+                        write!(f, "for some methods only ")?;
+                    }
+                    write!(f, "(")?;
+                    if candidate.priority != 0 {
+                        write!(f, "priority: {}, ", candidate.priority)?;
+                    }
+                    writeln!(
+                        f,
+                        "statics: {}, dynamics: {}, wildcards: {})",
+                        candidate.statics, candidate.dynamics, candidate.wildcards
+                    )?
+                }
+                Outcome::Rejected {
+                    ref param,
+                    ref validator,
+                } => writeln!(
+                    f,
+                    "  -  {} matched, but validator {} rejected {}",
+                    candidate.pattern, validator, param
+                )?,
+                Outcome::Failed {
+                    offset,
+                    ref expected,
+                } => writeln!(
+                    f,
+                    "  -  {} failed at offset {}, expected {}",
+                    candidate.pattern,
+                    offset,
+                    if expected.is_empty() {
+                        "the end of the path".to_string()
+                    } else {
+                        expected.join(" or ")
+                    }
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A route considered by `Router::explain`.
+#[derive(Clone, Debug)]
+pub struct Candidate {
+    pattern: String,
+    priority: i32,
+    statics: u32,
+    dynamics: u32,
+    wildcards: u32,
+    handles_any: bool,
+    outcome: Outcome,
+}
+
+impl Candidate {
+    /// Get the pattern of the route.
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Get the priority the route was added with, which is compared before
+    /// anything else.
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    /// Get the number of static segments, which `Ranking::Counts` compares
+    /// first.
+    pub fn statics(&self) -> u32 {
+        self.statics
+    }
+
+    /// Get the number of param segments, which `Ranking::Counts` compares
+    /// second.
+    pub fn dynamics(&self) -> u32 {
+        self.dynamics
+    }
+
+    /// Get the number of wildcard segments, which `Ranking::Counts` compares
+    /// last.
+    pub fn wildcards(&self) -> u32 {
+        self.wildcards
+    }
+
+    /// Returns `true` if the route has a handler for any method, which
+    /// `recognize` requires.
+    pub fn handles_any(&self) -> bool {
+        self.handles_any
+    }
+
+    /// Get whether and how the route matched.
+    pub fn outcome(&self) -> &Outcome {
+        &self.outcome
+    }
+}
+
+/// Whether a route matched the path given to `Router::explain`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Outcome {
+    /// The route matched the path.
+    Matched {
+        /// The position of the route among the matching routes, where `0` is
+        /// the highest-ranked route. `recognize` picks the highest-ranked
+        /// route with a handler for any method, see `Explanation::winner`.
+        rank: usize,
+    },
+    /// The route matched the path, but a validator rejected one of its
+    /// params.
+    Rejected {
+        /// The name of the rejected param.
+        param: String,
+        /// The name of the validator that rejected it.
+        validator: String,
+    },
+    /// The route did not match the path.
+    Failed {
+        /// The number of characters of the path the route matched before it
+        /// stopped matching.
+        offset: usize,
+        /// The character classes the route would have accepted at `offset`,
+        /// or the allowed values where an enumerated param starts. Empty
+        /// when the route only accepts the end of the path there.
+        expected: Vec<String>,
+    },
+}
+
+impl Outcome {
+    /// Returns `true` if the route matched.
+    pub fn is_match(&self) -> bool {
+        matches!(self, Outcome::Matched { .. })
+    }
+}
+
+impl<T> Router<T> {
+    /// Explain how `path` is matched against every route of the router.
+    ///
+    /// Routes that only have handlers for some methods are ranked too, since
+    /// `recognize_with_method` may pick them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use route_recognizer::{Outcome, Router};
+    ///
+    /// let mut router = Router::new();
+    /// router.add("/posts/:id", "show");
+    /// router.add("/posts/new", "new");
+    /// router.add("/comments", "comments");
+    ///
+    /// let explanation = router.explain("/posts/new");
+    /// assert_eq!(explanation.winner().unwrap().pattern(), "/posts/new");
+    ///
+    /// let comments = &explanation.candidates()[2];
+    /// assert_eq!(comments.pattern(), "/comments");
+    /// assert_eq!(
+    ///     *comments.outcome(),
+    ///     Outcome::Failed { offset: 1, expected: vec!["c".to_string()] }
+    /// );
+    ///
+    /// println!("{}", explanation);
+    /// ```
+    pub fn explain(&self, route: &str) -> Explanation {
+        let mut path = route;
+        let mut skipped = 0;
+        if !path.is_empty() && path.as_bytes()[0] == b'/' {
+            path = &path[1..];
+            skipped = 1;
+        }
+
+        let nfa = &self.nfa;
+        let matches = self.candidates(path);
+        let rejected: Vec<_> = nfa
+            .process_all(path, |index| self.rank(index))
+            .into_iter()
+            .filter_map(|m| Some((m.state, self.rejection(&m)?)))
+            .collect();
+
+        let mut matched = vec![];
+        let mut failed = vec![];
+
+        for &state in self.handlers.keys() {
+            let metadata = nfa.get(state).metadata.as_ref().unwrap();
+            let rank = matches.iter().position(|m| m.state == state);
+            let rejection = rejected.iter().find(|&&(other, _)| other == state);
+            let outcome = match (rank, rejection) {
+                (Some(rank), _) => Outcome::Matched { rank },
+                (None, Some(&(_, (param, validator)))) => Outcome::Rejected {
+                    param: param.to_string(),
+                    validator: validator.to_string(),
+                },
+                (None, None) => {
+                    let trace = nfa.trace(path, state);
+                    let mut expected: Vec<String> = trace
+                        .expected
+                        .iter()
+                        .map(|&index| {
+                            let state = nfa.get(index);
+                            match choice_of(state) {
+                                Some(choice) => choice.to_string(),
+                                None => state.chars.to_string(),
+                            }
+                        })
+                        .collect();
+                    expected.sort();
+                    expected.dedup();
+
+                    Outcome::Failed {
+                        offset: trace.consumed + skipped,
+                        expected,
+                    }
+                }
+            };
+
+            let candidate = Candidate {
+                pattern: metadata.route.clone(),
+                priority: metadata.priority,
+                statics: metadata.statics,
+                dynamics: metadata.dynamics,
+                wildcards: metadata.wildcards,
+                handles_any: self.handlers[&state].any.is_some(),
+                outcome,
+            };
+            match rank {
+                Some(rank) => matched.push((rank, candidate)),
+                None => failed.push(candidate),
+            }
+        }
+
+        matched.sort_by_key(|&(rank, _)| rank);
+        failed.sort_by(|a, b| a.pattern.cmp(&b.pattern));
+
+        let mut candidates: Vec<Candidate> = matched.into_iter().map(|(_, c)| c).collect();
+        candidates.extend(failed);
+
+        Explanation {
+            path: route.to_string(),
+            candidates,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Outcome;
+    use crate::{Method, Router};
+
+    #[test]
+    fn explain() {
+        let mut router = Router::new();
+        router.add("/posts/:id", ());
+        router.add("/posts/new", ());
+        router.add("/posts/:id/edit", ());
+        router.add("/*path", ());
+
+        let explanation = router.explain("/posts/new");
+        let outcomes: Vec<_> = explanation
+            .candidates()
+            .iter()
+            .map(|c| (c.pattern(), c.outcome().clone()))
+            .collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                ("/posts/new", Outcome::Matched { rank: 0 }),
+                ("/posts/:id", Outcome::Matched { rank: 1 }),
+                ("/*path", Outcome::Matched { rank: 2 }),
+                (
+                    "/posts/:id/edit",
+                    Outcome::Failed {
+                        offset: 10,
+                        expected: vec!["/".to_string(), "[^/]".to_string()],
+                    }
+                ),
+            ]
+        );
+
+        assert_eq!(
+            explanation.to_string(),
+            "path: /posts/new\n\
+             \x20 1. /posts/new matched (statics: 2, dynamics: 0, wildcards: 0)\n\
+             \x20 2. /posts/:id matched (statics: 1, dynamics: 1, wildcards: 0)\n\
+             \x20 3. /*path matched (statics: 0, dynamics: 0, wildcards: 1)\n\
+             \x20 -  /posts/:id/edit failed at offset 10, expected / or [^/]\n"
+        );
+    }
+
+    #[test]
+    fn explain_methods() {
+        let mut router = Router::new();
+        router.add_with_method(Method::GET, "/posts/new", "new");
+        router.add("/posts/:id", "show");
+
+        let explanation = router.explain("/posts/new");
+        let winner = explanation.winner().unwrap();
+        assert_eq!(winner.pattern(), "/posts/:id");
+        assert_eq!(*winner.outcome(), Outcome::Matched { rank: 1 });
+        assert!(!explanation.candidates()[0].handles_any());
+        assert_eq!(*router.recognize("/posts/new").unwrap().handler(), &"show");
+        assert_eq!(
+            explanation.to_string(),
+            "path: /posts/new\n\
+             \x20 1. /posts/new matched for some methods only (statics: 2, dynamics: 0, wildcards: 0)\n\
+             \x20 2. /posts/:id matched (statics: 1, dynamics: 1, wildcards: 0)\n"
+        );
+    }
+
+    #[test]
+    fn explain_no_match() {
+        let mut router = Router::new();
+        router.add("/posts", ());
+
+        let explanation = router.explain("/posts/1");
+        assert!(explanation.winner().is_none());
+        assert_eq!(
+            *explanation.candidates()[0].outcome(),
+            Outcome::Failed {
+                offset: 6,
+                expected: vec![],
+            }
+        );
+    }
+}
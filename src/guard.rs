@@ -0,0 +1,206 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::sync::Arc;
+
+use crate::{Match, Router};
+
+/// The request context that guards are checked against.
+#[derive(Clone, Debug, Default)]
+pub struct Context {
+    headers: BTreeMap<String, String>,
+    flags: BTreeSet<String>,
+}
+
+impl Context {
+    /// Create a new, empty instance of `Context`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Insert a header into the context. Header names are case-insensitive.
+    pub fn insert_header(&mut self, name: &str, value: &str) {
+        self.headers
+            .insert(name.to_ascii_lowercase(), value.to_string());
+    }
+
+    /// Find a header by name in the context.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(&name.to_ascii_lowercase()).map(|s| &s[..])
+    }
+
+    /// Enable a feature flag in the context.
+    pub fn insert_flag(&mut self, flag: &str) {
+        self.flags.insert(flag.to_string());
+    }
+
+    /// Returns `true` if the feature flag is enabled.
+    pub fn has_flag(&self, flag: &str) -> bool {
+        self.flags.contains(flag)
+    }
+}
+
+/// A predicate that decides whether a route may handle a request.
+///
+/// Closures that take a `Context` and return a `bool` implement this trait.
+pub trait Guard: Send + Sync {
+    /// Returns `true` if the route may handle a request with `context`.
+    fn check(&self, context: &Context) -> bool;
+}
+
+impl<F> Guard for F
+where
+    F: Fn(&Context) -> bool + Send + Sync,
+{
+    fn check(&self, context: &Context) -> bool {
+        self(context)
+    }
+}
+
+/// A handler that is only used when its guard accepts the request.
+#[derive(Clone)]
+pub(crate) struct Guarded<T> {
+    guard: Arc<dyn Guard>,
+    handler: T,
+}
+
+impl<T: fmt::Debug> fmt::Debug for Guarded<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Guarded")
+            .field("handler", &self.handler)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> Router<T> {
+    /// Add a route to the router that only handles requests its `guard`
+    /// accepts.
+    ///
+    /// Guarded handlers are only considered by `recognize_with_context`. They
+    /// are tried in the order they were added, before the route's handler for
+    /// any method. They are not listed by `routes` and are not serialized.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use route_recognizer::{Context, Router};
+    ///
+    /// let mut router = Router::new();
+    /// router.add("/posts/:id", "v1");
+    /// router.add_with_guard(
+    ///     "/posts/:id",
+    ///     |context: &Context| context.header("Accept") == Some("application/vnd.v2"),
+    ///     "v2",
+    /// );
+    ///
+    /// let mut context = Context::new();
+    /// let m = router.recognize_with_context(&context, "/posts/1").unwrap();
+    /// assert_eq!(*m.handler(), &"v1");
+    ///
+    /// context.insert_header("accept", "application/vnd.v2");
+    /// let m = router.recognize_with_context(&context, "/posts/1").unwrap();
+    /// assert_eq!(*m.handler(), &"v2");
+    /// ```
+    pub fn add_with_guard<G: Guard + 'static>(&mut self, route: &str, guard: G, dest: T) {
+        let state = self.insert(route);
+        self.endpoint(state).guarded.push(Guarded {
+            guard: Arc::new(guard),
+            handler: dest,
+        });
+    }
+
+    /// Match a route on the router for a request with `context`.
+    ///
+    /// Routes are tried in priority order. The first guarded handler whose
+    /// guard accepts `context` wins, or else the route's handler for any
+    /// method. If a route has neither, the next-best route is tried.
+    pub fn recognize_with_context(
+        &self,
+        context: &Context,
+        mut path: &str,
+    ) -> Result<Match<&T>, String> {
+        if !path.is_empty() && path.as_bytes()[0] == b'/' {
+            path = &path[1..];
+        }
+
+        for nfa_match in self.candidates(path) {
+            let endpoint = &self.handlers[&nfa_match.state];
+            let handler = endpoint
+                .guarded
+                .iter()
+                .find(|guarded| guarded.guard.check(context))
+                .map(|guarded| &guarded.handler)
+                .or(endpoint.any.as_ref());
+
+            if let Some(handler) = handler {
+                return Ok(Match::new(handler, self.params(&nfa_match)));
+            }
+        }
+
+        Err(format!("No route accepts {}", path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Context;
+    use crate::{Method, RecognizeError, Router};
+
+    #[test]
+    fn guards() {
+        let mut router = Router::new();
+        router.add_with_guard("/posts/new", |c: &Context| c.has_flag("editor"), "editor");
+        router.add("/posts/:id", "post");
+        router.add_with_guard("/posts/:id", |c: &Context| c.has_flag("canary"), "canary");
+
+        let mut context = Context::new();
+        let m = router
+            .recognize_with_context(&context, "/posts/new")
+            .unwrap();
+        assert_eq!(*m.handler(), &"post");
+        assert_eq!(m.params().find("id"), Some("new"));
+        assert_eq!(*router.recognize("/posts/new").unwrap().handler(), &"post");
+
+        context.insert_flag("editor");
+        let m = router
+            .recognize_with_context(&context, "/posts/new")
+            .unwrap();
+        assert_eq!(*m.handler(), &"editor");
+
+        context.insert_flag("canary");
+        let m = router.recognize_with_context(&context, "/posts/1").unwrap();
+        assert_eq!(*m.handler(), &"canary");
+
+        assert!(router.recognize_with_context(&context, "/posts").is_err());
+    }
+
+    #[test]
+    fn guards_with_methods() {
+        let mut router = Router::new();
+        router.add_with_guard("/g", |c: &Context| c.has_flag("beta"), "beta");
+        assert_eq!(
+            router
+                .recognize_with_method(&Method::GET, "/g")
+                .unwrap_err(),
+            RecognizeError::NotFound
+        );
+
+        router.add_with_method(Method::POST, "/g", "post");
+        assert_eq!(
+            router
+                .recognize_with_method(&Method::GET, "/g")
+                .unwrap_err(),
+            RecognizeError::MethodNotAllowed {
+                allowed: vec![Method::POST]
+            }
+        );
+    }
+
+    #[test]
+    fn context_headers() {
+        let mut context = Context::new();
+        context.insert_header("Accept", "text/html");
+        assert_eq!(context.header("ACCEPT"), Some("text/html"));
+        assert_eq!(context.header("Host"), None);
+        assert!(!context.has_flag("canary"));
+    }
+}
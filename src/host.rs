@@ -0,0 +1,198 @@
+use std::collections::BTreeMap;
+
+use crate::nfa::NFA;
+use crate::{compile, Dots, Match, Metadata, Params, Router};
+
+/// Recognizes host and path patterns together.
+///
+/// Host patterns use `.` as the separator and are matched from right to left,
+/// so `:tenant.example.com` and `*.example.com` both match
+/// `acme.example.com`. Params from the host and from the path end up in the
+/// same `Params`.
+///
+/// # Examples
+///
+/// ```
+/// use route_recognizer::HostRouter;
+///
+/// let mut router = HostRouter::new();
+/// router.add(":tenant.example.com", "/posts/:id", "post");
+/// router.add("*.example.com", "/*path", "fallback");
+///
+/// let m = router.recognize("acme.example.com", "/posts/1").unwrap();
+/// assert_eq!(*m.handler(), &"post");
+/// assert_eq!(m.params().find("tenant"), Some("acme"));
+/// assert_eq!(m.params().find("id"), Some("1"));
+/// ```
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HostRouter<T> {
+    nfa: NFA<Metadata>,
+    routers: BTreeMap<usize, Router<T>>,
+}
+
+impl<T> HostRouter<T> {
+    /// Create a new instance of `HostRouter`.
+    pub fn new() -> Self {
+        Self {
+            nfa: NFA::new(),
+            routers: BTreeMap::new(),
+        }
+    }
+
+    /// Add a route for the given host pattern to the router.
+    ///
+    /// Like the hosts it is matched against, the pattern is lowercased,
+    /// except for the names of its params, and a trailing `.` is ignored.
+    pub fn add(&mut self, host: &str, route: &str, dest: T) {
+        let host = normalize_pattern(host);
+        let reversed = reverse_labels(&host);
+        let state = compile(&mut self.nfa, &host, &reversed, &['.'], '.', Dots::Greedy);
+        self.routers.entry(state).or_default().add(route, dest);
+    }
+
+    /// Match a host and path on the router.
+    ///
+    /// The host is matched case-insensitively, and a trailing port is ignored.
+    /// Hosts are tried in priority order until one of them has a route that
+    /// matches the path.
+    pub fn recognize(&self, host: &str, path: &str) -> Result<Match<&T>, String> {
+        let reversed = reverse_labels(&normalize(host));
+        let nfa = &self.nfa;
+        let matches = nfa.process_all(&reversed, |index| nfa.get(index).metadata.as_ref().unwrap());
+
+        if matches.is_empty() {
+            return Err(format!("No host pattern matches {}", host));
+        }
+
+        for host_match in matches {
+            let router = &self.routers[&host_match.state];
+            let path_match = match router.recognize(path) {
+                Ok(path_match) => path_match,
+                Err(_) => continue,
+            };
+
+            let metadata = nfa.get(host_match.state).metadata.as_ref().unwrap();
+            let mut params = Params::new();
+            for (name, capture) in metadata.param_names.iter().zip(&host_match.captures) {
+                if name.is_empty() {
+                    params.push(reverse_labels(capture));
+                } else {
+                    params.insert(name.to_string(), reverse_labels(capture));
+                }
+            }
+
+            let handler = *path_match.handler();
+            params.append(path_match.params);
+            return Ok(Match::new(handler, params));
+        }
+
+        Err(format!("No route matches {} on this host", path))
+    }
+}
+
+impl<T> Default for HostRouter<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn normalize(mut host: &str) -> String {
+    if let Some(i) = host.rfind(':') {
+        let port = &host[i + 1..];
+        if !port.is_empty() && port.bytes().all(|b| b.is_ascii_digit()) {
+            host = &host[..i];
+        }
+    }
+
+    host.trim_end_matches('.').to_lowercase()
+}
+
+fn normalize_pattern(host: &str) -> String {
+    let labels = host.trim_end_matches('.').split('.').map(|label| {
+        if label.starts_with(':') || label.starts_with('*') {
+            label.to_string()
+        } else {
+            label.to_lowercase()
+        }
+    });
+    labels.collect::<Vec<_>>().join(".")
+}
+
+fn reverse_labels(host: &str) -> String {
+    host.split('.').rev().collect::<Vec<_>>().join(".")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HostRouter;
+
+    #[test]
+    fn host_params() {
+        let mut router = HostRouter::new();
+        router.add(":tenant.example.com", "/posts/:id", "post");
+        router.add("api.example.com", "/posts/:id", "api");
+
+        let m = router.recognize("acme.example.com", "/posts/1").unwrap();
+        assert_eq!(*m.handler(), &"post");
+        assert_eq!(m.params().find("tenant"), Some("acme"));
+        assert_eq!(m.params().find("id"), Some("1"));
+        assert_eq!(m.params().get(0), Some(("tenant", "acme")));
+
+        let m = router
+            .recognize("API.example.com:8080", "/posts/1")
+            .unwrap();
+        assert_eq!(*m.handler(), &"api");
+        assert_eq!(m.params().find("tenant"), None);
+
+        assert!(router.recognize("a.b.example.com", "/posts/1").is_err());
+        assert!(router.recognize("example.org", "/posts/1").is_err());
+    }
+
+    #[test]
+    fn host_wildcards() {
+        let mut router = HostRouter::new();
+        router.add("*sub.example.com", "/", "sub");
+        router.add("*.example.org", "/about", "about");
+
+        let m = router.recognize("a.b.example.com", "/").unwrap();
+        assert_eq!(*m.handler(), &"sub");
+        assert_eq!(m.params().find("sub"), Some("a.b"));
+
+        let m = router.recognize("a.b.example.org", "/about").unwrap();
+        assert_eq!(*m.handler(), &"about");
+        assert_eq!(m.params().iter().count(), 0);
+        assert_eq!(m.params().positional(0), Some("a.b"));
+
+        assert!(router.recognize("example.com", "/").is_err());
+    }
+
+    #[test]
+    fn host_pattern_case() {
+        let mut router = HostRouter::new();
+        router.add("API.Example.com.", "/", "api");
+        router.add(":Tenant.Example.com", "/", "tenant");
+
+        assert_eq!(
+            *router.recognize("api.example.com", "/").unwrap().handler(),
+            &"api"
+        );
+        assert_eq!(
+            *router.recognize("API.EXAMPLE.COM.", "/").unwrap().handler(),
+            &"api"
+        );
+        let m = router.recognize("acme.example.com", "/").unwrap();
+        assert_eq!(m.params().find("Tenant"), Some("acme"));
+    }
+
+    #[test]
+    fn host_falls_through() {
+        let mut router = HostRouter::new();
+        router.add("www.example.com", "/", "home");
+        router.add(":tenant.example.com", "/dashboard", "dashboard");
+
+        let m = router.recognize("www.example.com", "/dashboard").unwrap();
+        assert_eq!(*m.handler(), &"dashboard");
+        assert_eq!(m.params().find("tenant"), Some("www"));
+    }
+}
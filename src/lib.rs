@@ -32,8 +32,36 @@
 //! However if we define the route `/a/*b` and we pass `/foo/bar/baz` we end up
 //! with a named param `"b"` that contains the value `"bar/baz"`. Wildcard
 //! routing rules are useful when you don't know which routes may follow. The
-//! difference between "named" and "unnamed" wildcards is that the former will
-//! show up in `Params`, while the latter won't.
+//! difference between "named" and "unnamed" wildcards is that the former can
+//! be looked up by name in `Params`, while the latter can only be read by
+//! position with `Params::positional`.
+//!
+//! # Param constraints
+//!
+//! Params can be constrained to the values they may capture. Constraints can
+//! be combined and follow the name in any order, as in `:name{1,8}[^.]<slug>`:
+//! - __validators__: `/posts/:id<uuid>` only matches if the validator that
+//!   was registered as `uuid` with `Router::add_validator` accepts the value.
+//! - __enumerated params__: `/report.:format(json|xml|csv)` only matches one
+//!   of the listed values. Adding a route with an empty list panics.
+//! - __length bounds__: `/:lang{2,3}` only matches values of two or three
+//!   characters. `{n}`, `{min,}` and `{..max}` are accepted too. A param
+//!   always matches at least one character, and adding a route with a lower
+//!   bound of `0` panics.
+//! - __wildcard bounds__: `/docs/*path{1,3}` only matches one to three
+//!   segments, and `/assets/*path?` also matches `/assets/` and `/assets`,
+//!   with an empty `path`. Only a wildcard at the end of a route can be
+//!   optional, and adding a route with another optional wildcard panics.
+//! - __dots__: params match dots by default, so `/:name.:ext` splits
+//!   `archive.tar.gz` on the last dot. `/:name[^.].:ext` stops `name` at the
+//!   first dot instead, and `Router::set_dots` excludes dots from every param
+//!   of the routes added afterwards.
+//!
+//! # Features
+//!
+//! - __serde__: implements `Serialize` and `Deserialize` for `Router` and
+//!   `HostRouter`. The compiled automaton is serialized, so deserializing a
+//!   router does not parse its patterns again.
 
 #![cfg_attr(feature = "docs", feature(doc_cfg))]
 #![deny(unsafe_code)]
@@ -45,29 +73,61 @@
 #![doc(html_logo_url = "https://yoshuawuyts.com/assets/http-rs/logo-rounded.png")]
 
 use std::cmp::Ordering;
-use std::collections::{btree_map, BTreeMap};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
 use std::ops::Index;
+use std::sync::Arc;
 
+use crate::guard::Guarded;
 use crate::nfa::{CharacterClass, NFA};
 
+pub use crate::explain::{Candidate, Explanation, Outcome};
+pub use crate::guard::{Context, Guard};
+pub use crate::host::HostRouter;
+pub use crate::lint::{Ambiguity, Example, Shadowed};
+pub use crate::method::Method;
+pub use crate::validate::Validator;
+
+mod explain;
+mod guard;
+mod host;
+mod lint;
+mod method;
 #[doc(hidden)]
 pub mod nfa;
+mod validate;
 
 #[derive(Clone, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct Metadata {
     statics: u32,
     dynamics: u32,
     wildcards: u32,
     param_names: Vec<String>,
+    validators: Vec<Option<String>>,
+    choices: Vec<Vec<String>>,
+    kinds: Vec<SegmentKind>,
+    route: String,
+    priority: i32,
+    sequence: usize,
+    dots: Dots,
 }
 
 impl Metadata {
-    pub(crate) fn new() -> Self {
+    pub(crate) fn new(route: &str) -> Self {
         Self {
             statics: 0,
             dynamics: 0,
             wildcards: 0,
             param_names: Vec::new(),
+            validators: Vec::new(),
+            choices: Vec::new(),
+            kinds: Vec::new(),
+            route: route.to_string(),
+            priority: 0,
+            sequence: 0,
+            dots: Dots::default(),
         }
     }
 }
@@ -106,44 +166,381 @@ impl PartialEq for Metadata {
     }
 }
 
+/// The kind of a route segment, from the least to the most specific.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SegmentKind {
+    /// A wildcard segment, such as `*path`.
+    Wildcard,
+    /// A param segment, such as `:id`.
+    Dynamic,
+    /// A static segment, such as `posts`.
+    Static,
+}
+
+/// How to decide which route wins when several of them match a path.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Ranking {
+    /// The route with the most static segments wins, then the one with the
+    /// most params, then the one with the most wildcards. This is the default.
+    #[default]
+    Counts,
+    /// Segments are compared from left to right, and the route with the more
+    /// specific segment at the first position where they differ wins. A
+    /// static segment beats a param, which beats a wildcard. If one route
+    /// runs out of segments first, the longer route wins.
+    Positional,
+}
+
+/// How to order routes that match a path with equal priority.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TieBreak {
+    /// The route that was added first wins. This is the default.
+    #[default]
+    FirstAdded,
+    /// The route that was added last wins.
+    LastAdded,
+}
+
+/// Orders the routes that match a path, in place of the router's `Ranking`.
+///
+/// Closures that take two `RouteInfo`s and return an `Ordering` implement this
+/// trait.
+pub trait RouteRanker: Send + Sync {
+    /// Compare two routes that match the same path. `Ordering::Greater` means
+    /// that `first` wins.
+    ///
+    /// Routes that compare equal are ordered by the router's `TieBreak`. The
+    /// comparison must be a total order: the matching routes are sorted with
+    /// `sort_by`, which may panic otherwise since Rust 1.81.
+    fn compare(&self, first: &RouteInfo<'_>, second: &RouteInfo<'_>) -> Ordering;
+}
+
+impl<F> RouteRanker for F
+where
+    F: Fn(&RouteInfo<'_>, &RouteInfo<'_>) -> Ordering + Send + Sync,
+{
+    fn compare(&self, first: &RouteInfo<'_>, second: &RouteInfo<'_>) -> Ordering {
+        self(first, second)
+    }
+}
+
+/// A route handed to a `RouteRanker`.
+#[derive(Clone, Copy, Debug)]
+pub struct RouteInfo<'a> {
+    metadata: &'a Metadata,
+    captures: &'a [&'a str],
+}
+
+impl<'a> RouteInfo<'a> {
+    /// Get the pattern the route was added with.
+    pub fn pattern(&self) -> &'a str {
+        &self.metadata.route
+    }
+
+    /// Get the kinds of the route's segments, in path order.
+    pub fn kinds(&self) -> &'a [SegmentKind] {
+        &self.metadata.kinds
+    }
+
+    /// Get the values captured by the route's params and wildcards, in path
+    /// order, including unnamed ones.
+    ///
+    /// This is empty when routes are compared without a path, as
+    /// `recognize_prefix` and `lint` do.
+    pub fn captures(&self) -> &'a [&'a str] {
+        self.captures
+    }
+
+    /// Get the priority the route was added with.
+    pub fn priority(&self) -> i32 {
+        self.metadata.priority
+    }
+}
+
+/// A `RouteRanker` shared between clones of a router.
+#[derive(Clone)]
+struct Ranker(Arc<dyn RouteRanker>);
+
+impl fmt::Debug for Ranker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Ranker").finish()
+    }
+}
+
+/// How an optional wildcard that matched nothing shows up in `Params`.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EmptyWildcard {
+    /// The wildcard is present with an empty value. This is the default.
+    #[default]
+    Present,
+    /// The wildcard is absent.
+    Absent,
+}
+
+/// Whether dynamic segments match dots.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Dots {
+    /// Dynamic segments match dots, so `:name.:ext` splits on the last dot.
+    /// This is the default.
+    #[default]
+    Greedy,
+    /// Dynamic segments never match dots.
+    Excluded,
+}
+
+/// The key routes are ordered by when several of them match a path.
+#[derive(Clone, Copy)]
+struct Rank<'a> {
+    metadata: &'a Metadata,
+    captures: &'a [&'a str],
+    ranking: Ranking,
+    ranker: Option<&'a dyn RouteRanker>,
+    tie_break: TieBreak,
+}
+
+impl Rank<'_> {
+    fn info(&self) -> RouteInfo<'_> {
+        RouteInfo {
+            metadata: self.metadata,
+            captures: self.captures,
+        }
+    }
+
+    /// Compare the priority of two routes, without breaking ties.
+    fn priority(&self, other: &Self) -> Ordering {
+        if let Some(ranker) = self.ranker {
+            return ranker.compare(&self.info(), &other.info());
+        }
+
+        let (first, second) = (self.metadata, other.metadata);
+        first
+            .priority
+            .cmp(&second.priority)
+            .then_with(|| match self.ranking {
+                Ranking::Counts => first.cmp(second),
+                Ranking::Positional => first.kinds.cmp(&second.kinds),
+            })
+    }
+}
+
+impl Ord for Rank<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let (first, second) = (self.metadata.sequence, other.metadata.sequence);
+        self.priority(other).then_with(|| match self.tie_break {
+            TieBreak::FirstAdded => second.cmp(&first),
+            TieBreak::LastAdded => first.cmp(&second),
+        })
+    }
+}
+
+impl PartialOrd for Rank<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Rank<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Rank<'_> {}
+
 /// Router parameters.
-#[derive(PartialEq, Clone, Debug, Default)]
+///
+/// Params are kept in the order they were inserted, which for a match is the
+/// order they appear in the path. Two `Params` are equal if they have the same
+/// named params in the same order; unnamed params are ignored.
+#[derive(Clone, Debug, Default)]
 pub struct Params {
-    map: BTreeMap<String, String>,
+    entries: Vec<(String, String)>,
 }
 
 impl Params {
     /// Create a new instance of `Params`.
     pub fn new() -> Self {
         Self {
-            map: BTreeMap::new(),
+            entries: Vec::new(),
         }
     }
 
     /// Insert a new param into `Params`.
+    ///
+    /// Inserting a name that already exists keeps both values: `find` returns
+    /// the new one, and `get_all` returns both.
     pub fn insert(&mut self, key: String, value: String) {
-        self.map.insert(key, value);
+        self.entries.push((key, value));
+    }
+
+    /// Append an unnamed param, which can only be accessed positionally.
+    pub fn push(&mut self, value: String) {
+        self.entries.push((String::new(), value));
     }
 
     /// Find a param by name in `Params`.
+    ///
+    /// If the name was inserted more than once, the last value is returned.
     pub fn find(&self, key: &str) -> Option<&str> {
-        self.map.get(key).map(|s| &s[..])
+        if key.is_empty() {
+            return None;
+        }
+        self.entries
+            .iter()
+            .rev()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| &value[..])
+    }
+
+    /// Find every value of a param by name, in insertion order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use route_recognizer::Router;
+    ///
+    /// let mut router = Router::new();
+    /// router.add("/users/:id/friends/:id", "friend");
+    ///
+    /// let m = router.recognize("/users/1/friends/2").unwrap();
+    /// assert_eq!(m.params().get_all("id"), vec!["1", "2"]);
+    /// assert_eq!(m.params().find("id"), Some("2"));
+    /// ```
+    pub fn get_all(&self, key: &str) -> Vec<&str> {
+        if key.is_empty() {
+            return Vec::new();
+        }
+
+        self.entries
+            .iter()
+            .filter(|(name, _)| name == key)
+            .map(|(_, value)| &value[..])
+            .collect()
+    }
+
+    /// Find a param by name and split its value into segments, as for a
+    /// wildcard.
+    ///
+    /// An empty value has no segments.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use route_recognizer::Router;
+    ///
+    /// let mut router = Router::new();
+    /// router.add("/assets/*path?", "assets");
+    ///
+    /// let m = router.recognize("/assets/css/main.css").unwrap();
+    /// assert_eq!(m.params().find("path"), Some("css/main.css"));
+    /// assert_eq!(m.params().segments("path"), Some(vec!["css", "main.css"]));
+    ///
+    /// let m = router.recognize("/assets/").unwrap();
+    /// assert_eq!(m.params().segments("path"), Some(vec![]));
+    /// ```
+    pub fn segments(&self, key: &str) -> Option<Vec<&str>> {
+        let value = self.find(key)?;
+        if value.is_empty() {
+            return Some(Vec::new());
+        }
+        Some(value.split('/').collect())
+    }
+
+    /// Find a param by its position in the path, counting named and unnamed
+    /// params and wildcards alike.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use route_recognizer::Router;
+    ///
+    /// let mut router = Router::new();
+    /// router.add("/static/:version/*", "static");
+    ///
+    /// let m = router.recognize("/static/v1/css/main.css").unwrap();
+    /// assert_eq!(m.params().positional(0), Some("v1"));
+    /// assert_eq!(m.params().positional(1), Some("css/main.css"));
+    /// assert_eq!(m.params().positional(2), None);
+    /// ```
+    pub fn positional(&self, index: usize) -> Option<&str> {
+        self.entries.get(index).map(|(_, value)| &value[..])
+    }
+
+    /// Find the name and value of a param by its position in the path.
+    ///
+    /// The name of an unnamed param or wildcard is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use route_recognizer::Router;
+    ///
+    /// let mut router = Router::new();
+    /// router.add("/:year/:month/*", "archive");
+    ///
+    /// let m = router.recognize("/2024/05/post").unwrap();
+    /// assert_eq!(m.params().get(1), Some(("month", "05")));
+    /// assert_eq!(m.params().get(2), Some(("", "post")));
+    /// assert_eq!(m.params()[0], "2024");
+    /// ```
+    pub fn get(&self, index: usize) -> Option<(&str, &str)> {
+        self.entries
+            .get(index)
+            .map(|(name, value)| (&name[..], &value[..]))
+    }
+
+    /// Get the number of params, named and unnamed.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if there are no params, named or unnamed.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
     }
 
-    /// Iterate over all named params.
+    /// Iterate over all named params, in insertion order.
     ///
-    /// This will return all named params and named wildcards.
+    /// This will return all named params and named wildcards. A name that was
+    /// inserted more than once is returned once per value.
     pub fn iter(&self) -> Iter<'_> {
-        Iter(self.map.iter())
+        Iter(self.entries.iter())
+    }
+
+    /// Move the params of `other` after the params of `self`.
+    fn append(&mut self, other: Params) {
+        self.entries.extend(other.entries);
+    }
+}
+
+impl PartialEq for Params {
+    fn eq(&self, other: &Self) -> bool {
+        self.iter().eq(other.iter())
     }
 }
 
 impl Index<&str> for Params {
     type Output = String;
     fn index(&self, index: &str) -> &String {
-        match self.map.get(index) {
+        let entry = self.entries.iter().rev().find(|(name, _)| name == index);
+        match entry.filter(|(name, _)| !name.is_empty()) {
+            None => panic!("params[{}] did not exist", index),
+            Some((_, s)) => s,
+        }
+    }
+}
+
+impl Index<usize> for Params {
+    type Output = String;
+    fn index(&self, index: usize) -> &String {
+        match self.entries.get(index) {
             None => panic!("params[{}] did not exist", index),
-            Some(s) => s,
+            Some((_, s)) => s,
         }
     }
 }
@@ -159,18 +556,20 @@ impl<'a> IntoIterator for &'a Params {
 
 /// An iterator over `Params`.
 #[derive(Debug)]
-pub struct Iter<'a>(btree_map::Iter<'a, String, String>);
+pub struct Iter<'a>(std::slice::Iter<'a, (String, String)>);
 
 impl<'a> Iterator for Iter<'a> {
     type Item = (&'a str, &'a str);
 
     #[inline]
     fn next(&mut self) -> Option<(&'a str, &'a str)> {
-        self.0.next().map(|(k, v)| (&**k, &**v))
+        self.0
+            .find(|(name, _)| !name.is_empty())
+            .map(|(k, v)| (&**k, &**v))
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
-        self.0.size_hint()
+        (0, self.0.size_hint().1)
     }
 }
 
@@ -210,22 +609,237 @@ impl<T> Match<T> {
     }
 }
 
+/// The error returned by `Router::recognize_with_method`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum RecognizeError {
+    /// No route matches the path.
+    NotFound,
+    /// A route matches the path, but none of them handles the method.
+    MethodNotAllowed {
+        /// The methods the matching routes do handle, suitable for an `Allow`
+        /// header.
+        allowed: Vec<Method>,
+    },
+}
+
+impl fmt::Display for RecognizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecognizeError::NotFound => f.write_str("no route matches the path"),
+            RecognizeError::MethodNotAllowed { allowed } => {
+                f.write_str("method not allowed, expected one of: ")?;
+                for (i, method) in allowed.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}", method)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Error for RecognizeError {}
+
+/// The result of a successful match returned by `Router::recognize_prefix`.
+#[derive(Debug)]
+pub struct PrefixMatch<'a, T> {
+    handler: T,
+    params: Params,
+    prefix: &'a str,
+    rest: &'a str,
+}
+
+impl<'a, T> PrefixMatch<'a, T> {
+    /// Get a handle to the handler.
+    pub fn handler(&self) -> &T {
+        &self.handler
+    }
+
+    /// Get a handle to the params.
+    pub fn params(&self) -> &Params {
+        &self.params
+    }
+
+    /// Get the part of the path that was matched by the route.
+    pub fn prefix(&self) -> &'a str {
+        self.prefix
+    }
+
+    /// Get the part of the path that follows the matched prefix.
+    ///
+    /// This is either empty or starts with a `/`.
+    pub fn rest(&self) -> &'a str {
+        self.rest
+    }
+}
+
+/// A route registered on a `Router`, returned by `Router::routes`.
+#[derive(Debug)]
+pub struct Route<'a, T> {
+    method: Option<&'a Method>,
+    metadata: &'a Metadata,
+    handler: &'a T,
+}
+
+impl<'a, T> Route<'a, T> {
+    /// Get the pattern the route was added with.
+    pub fn pattern(&self) -> &'a str {
+        &self.metadata.route
+    }
+
+    /// Get the method the route was added for, or `None` if it handles any
+    /// method.
+    pub fn method(&self) -> Option<&'a Method> {
+        self.method
+    }
+
+    /// Get the names of the params and named wildcards, in path order.
+    pub fn param_names(&self) -> Vec<&'a str> {
+        self.metadata
+            .param_names
+            .iter()
+            .filter(|name| !name.is_empty())
+            .map(|name| &name[..])
+            .collect()
+    }
+
+    /// Get the values an enumerated param like `:format(json|xml)` allows.
+    ///
+    /// Returns `None` if the route has no enumerated param called `name`.
+    pub fn param_values(&self, name: &str) -> Option<Vec<&'a str>> {
+        let metadata = self.metadata;
+        let index = metadata
+            .param_names
+            .iter()
+            .zip(&metadata.choices)
+            .position(|(other, choices)| other == name && !choices.is_empty())?;
+        Some(metadata.choices[index].iter().map(|s| &s[..]).collect())
+    }
+
+    /// Get the priority the route was added with.
+    pub fn priority(&self) -> i32 {
+        self.metadata.priority
+    }
+
+    /// Get the number of static segments in the route.
+    pub fn statics(&self) -> u32 {
+        self.metadata.statics
+    }
+
+    /// Get the number of param segments in the route.
+    pub fn dynamics(&self) -> u32 {
+        self.metadata.dynamics
+    }
+
+    /// Get the number of wildcard segments in the route.
+    pub fn wildcards(&self) -> u32 {
+        self.metadata.wildcards
+    }
+
+    /// Get a handle to the handler.
+    pub fn handler(&self) -> &'a T {
+        self.handler
+    }
+}
+
+/// An iterator over the routes of a `Router`.
+#[derive(Debug)]
+pub struct Routes<'a, T>(std::vec::IntoIter<Route<'a, T>>);
+
+impl<'a, T> Iterator for Routes<'a, T> {
+    type Item = Route<'a, T>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Route<'a, T>> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+/// An iterator over the handlers of a `Router`, returned by
+/// `Router::iter_mut`.
+#[derive(Debug)]
+pub struct IterMut<'a, T>(std::vec::IntoIter<(&'a str, Option<&'a Method>, &'a mut T)>);
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = (&'a str, Option<&'a Method>, &'a mut T);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
 /// Recognizes URL patterns with support for dynamic and wildcard segments.
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Router<T> {
     nfa: NFA<Metadata>,
-    handlers: BTreeMap<usize, T>,
+    handlers: BTreeMap<usize, Endpoint<T>>,
+    ranking: Ranking,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    ranker: Option<Ranker>,
+    tie_break: TieBreak,
+    empty_wildcard: EmptyWildcard,
+    #[cfg_attr(feature = "serde", serde(default))]
+    dots: Dots,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    validators: BTreeMap<String, validate::Shared>,
+    sequence: usize,
+}
+
+/// The handlers registered for a single route.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Endpoint<T> {
+    any: Option<T>,
+    methods: BTreeMap<Method, T>,
+    #[cfg_attr(feature = "serde", serde(skip, default = "Vec::new"))]
+    guarded: Vec<Guarded<T>>,
 }
 
-fn segments(route: &str) -> Vec<(Option<char>, &str)> {
-    let predicate = |c| c == '.' || c == '/';
+impl<T> Endpoint<T> {
+    fn new() -> Self {
+        Self {
+            any: None,
+            methods: BTreeMap::new(),
+            guarded: Vec::new(),
+        }
+    }
+
+    fn get(&self, method: &Method) -> Option<&T> {
+        self.methods.get(method).or(self.any.as_ref())
+    }
+}
+
+fn segments<'a>(route: &'a str, separators: &[char]) -> Vec<(Option<char>, &'a str)> {
+    let predicate = |c| separators.contains(&c);
 
     let mut segments = vec![];
     let mut segment_start = 0;
 
     while segment_start < route.len() {
-        let segment_end = route[segment_start + 1..]
-            .find(predicate)
+        let rest = &route[segment_start + 1..];
+        let text = &route[segment_start..];
+        let param = text
+            .strip_prefix(predicate)
+            .unwrap_or(text)
+            .starts_with([':', '*']);
+        let end = if param {
+            param_end(rest, predicate)
+        } else {
+            rest.find(predicate)
+        };
+        let segment_end = end
             .map(|i| i + segment_start + 1)
             .unwrap_or_else(|| route.len());
         let potential_sep = route.chars().nth(segment_start);
@@ -241,84 +855,740 @@ fn segments(route: &str) -> Vec<(Option<char>, &str)> {
     segments
 }
 
+// Finds the separator that ends a param or wildcard segment. Constraints like
+// `(a|b)` may contain separators, unless they are never closed, in which case
+// the segment ends at the first separator.
+fn param_end(rest: &str, predicate: impl Fn(char) -> bool) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in rest.char_indices() {
+        if "(<{[".contains(c) {
+            depth += 1;
+        } else if ")>}]".contains(c) && depth > 0 {
+            depth -= 1;
+        } else if depth == 0 && predicate(c) {
+            return Some(i);
+        }
+    }
+
+    if depth == 0 {
+        None
+    } else {
+        rest.find(predicate)
+    }
+}
+
 impl<T> Router<T> {
     /// Create a new instance of `Router`.
     pub fn new() -> Self {
         Self {
             nfa: NFA::new(),
             handlers: BTreeMap::new(),
+            ranking: Ranking::default(),
+            ranker: None,
+            tie_break: TieBreak::default(),
+            empty_wildcard: EmptyWildcard::default(),
+            dots: Dots::default(),
+            validators: BTreeMap::new(),
+            sequence: 0,
         }
     }
 
-    /// Add a route to the router.
-    pub fn add(&mut self, mut route: &str, dest: T) {
-        if !route.is_empty() && route.as_bytes()[0] == b'/' {
-            route = &route[1..];
-        }
-
-        let nfa = &mut self.nfa;
-        let mut state = 0;
-        let mut metadata = Metadata::new();
-
-        for (separator, segment) in segments(route) {
-            if let Some(separator) = separator {
-                state = nfa.put(state, CharacterClass::valid_char(separator));
-            }
-
-            if !segment.is_empty() && segment.as_bytes()[0] == b':' {
-                state = process_dynamic_segment(nfa, state);
-                metadata.dynamics += 1;
-                metadata.param_names.push(segment[1..].to_string());
-            } else if !segment.is_empty() && segment.as_bytes()[0] == b'*' {
-                state = process_star_state(nfa, state);
-                metadata.wildcards += 1;
-                metadata.param_names.push(segment[1..].to_string());
-            } else {
-                state = process_static_segment(segment, nfa, state);
-                metadata.statics += 1;
-            }
-        }
-
-        nfa.acceptance(state);
-        nfa.metadata(state, metadata);
-        self.handlers.insert(state, dest);
+    /// Set how the router decides which route wins when several of them match
+    /// a path.
+    ///
+    /// This replaces any ranker set with `set_ranker`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use route_recognizer::{Ranking, Router};
+    ///
+    /// let mut router = Router::new();
+    /// router.add("/:user/posts/:id", "post");
+    /// router.add("/admin/*path", "admin");
+    /// assert_eq!(*router.recognize("/admin/posts/1").unwrap().handler(), &"post");
+    ///
+    /// router.set_ranking(Ranking::Positional);
+    /// assert_eq!(*router.recognize("/admin/posts/1").unwrap().handler(), &"admin");
+    /// ```
+    pub fn set_ranking(&mut self, ranking: Ranking) {
+        self.ranking = ranking;
+        self.ranker = None;
     }
 
-    /// Match a route on the router.
-    pub fn recognize(&self, mut path: &str) -> Result<Match<&T>, String> {
-        if !path.is_empty() && path.as_bytes()[0] == b'/' {
-            path = &path[1..];
-        }
-
-        let nfa = &self.nfa;
-        let result = nfa.process(path, |index| nfa.get(index).metadata.as_ref().unwrap());
-
-        match result {
-            Ok(nfa_match) => {
-                let mut map = Params::new();
-                let state = &nfa.get(nfa_match.state);
-                let metadata = state.metadata.as_ref().unwrap();
-                let param_names = metadata.param_names.clone();
-
-                for (i, capture) in nfa_match.captures.iter().enumerate() {
-                    if !param_names[i].is_empty() {
-                        map.insert(param_names[i].to_string(), capture.to_string());
-                    }
-                }
+    /// Order the routes that match a path with a custom `RouteRanker`, in
+    /// place of the router's `Ranking`.
+    ///
+    /// The ranker replaces the priorities given to `add_with_priority` too,
+    /// unless it reads them from `RouteInfo::priority`, and it must be a total
+    /// order, as `RouteRanker::compare` explains. The ranker is not
+    /// serialized, so it has to be set again after a router is deserialized.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use route_recognizer::{RouteInfo, Router};
+    ///
+    /// let mut router = Router::new();
+    /// router.add("/posts/:id", "post");
+    /// router.add("/posts/:slug/", "slug");
+    /// router.add("/*path", "fallback");
+    ///
+    /// // Prefer the route with the fewest segments, then the shortest captures.
+    /// router.set_ranker(|first: &RouteInfo<'_>, second: &RouteInfo<'_>| {
+    ///     let length = |route: &RouteInfo<'_>| -> usize {
+    ///         route.captures().iter().map(|c| c.len()).sum()
+    ///     };
+    ///     second
+    ///         .kinds()
+    ///         .len()
+    ///         .cmp(&first.kinds().len())
+    ///         .then(length(second).cmp(&length(first)))
+    /// });
+    /// assert_eq!(*router.recognize("/posts/1").unwrap().handler(), &"fallback");
+    /// ```
+    pub fn set_ranker<R: RouteRanker + 'static>(&mut self, ranker: R) {
+        self.ranker = Some(Ranker(Arc::new(ranker)));
+    }
 
-                let handler = self.handlers.get(&nfa_match.state).unwrap();
-                Ok(Match::new(handler, map))
-            }
-            Err(str) => Err(str),
-        }
+    /// Set how routes that match a path with equal priority are ordered.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use route_recognizer::{Router, TieBreak};
+    ///
+    /// let mut router = Router::new();
+    /// router.add("/:a/x", "a");
+    /// router.add("/x/:b", "b");
+    /// assert_eq!(*router.recognize("/x/x").unwrap().handler(), &"a");
+    ///
+    /// router.set_tie_break(TieBreak::LastAdded);
+    /// assert_eq!(*router.recognize("/x/x").unwrap().handler(), &"b");
+    /// ```
+    pub fn set_tie_break(&mut self, tie_break: TieBreak) {
+        self.tie_break = tie_break;
     }
-}
 
-impl<T> Default for Router<T> {
-    fn default() -> Self {
-        Self::new()
+    /// Set how an optional wildcard that matched nothing shows up in the
+    /// params of a match.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use route_recognizer::{EmptyWildcard, Router};
+    ///
+    /// let mut router = Router::new();
+    /// router.add("/app/*path?", "app");
+    /// assert_eq!(router.recognize("/app").unwrap().params().find("path"), Some(""));
+    ///
+    /// router.set_empty_wildcard(EmptyWildcard::Absent);
+    /// assert_eq!(router.recognize("/app").unwrap().params().find("path"), None);
+    /// ```
+    pub fn set_empty_wildcard(&mut self, empty_wildcard: EmptyWildcard) {
+        self.empty_wildcard = empty_wildcard;
     }
-}
+
+    /// Set whether dynamic segments of the routes added from now on match
+    /// dots. A single param can exclude dots with `:name[^.]` instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use route_recognizer::{Dots, Router};
+    ///
+    /// let mut router = Router::new();
+    /// router.add("/files/:name", "greedy");
+    /// router.set_dots(Dots::Excluded);
+    /// router.add("/images/:name", "excluded");
+    ///
+    /// assert!(router.recognize("/files/report.tar.gz").is_ok());
+    /// assert!(router.recognize("/images/logo.png").is_err());
+    /// assert!(router.recognize("/images/logo").is_ok());
+    /// ```
+    pub fn set_dots(&mut self, dots: Dots) {
+        self.dots = dots;
+    }
+
+    /// Add a route to the router.
+    ///
+    /// The route matches requests with any method, unless a handler for the
+    /// specific method was added with `add_with_method`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a wildcard that can match nothing, like `*path?` or
+    /// `*path{0,2}`, is not the last segment of the route. The other ways of
+    /// adding a route panic in that case too.
+    pub fn add(&mut self, route: &str, dest: T) {
+        let state = self.insert(route);
+        self.endpoint(state).any = Some(dest);
+    }
+
+    /// Add a route to the router with an explicit priority.
+    ///
+    /// Routes with a higher priority win over routes with a lower one,
+    /// regardless of their segments. Only routes with equal priority are
+    /// ordered by the router's `Ranking`.
+    ///
+    /// The priority belongs to the route, which all of its handlers share.
+    /// New routes added with `add` or `add_with_method` have a priority of
+    /// `0`, but adding another handler for an existing route keeps its
+    /// priority. Only `add_with_priority` changes it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use route_recognizer::Router;
+    ///
+    /// let mut router = Router::new();
+    /// router.add_with_priority("/*path", -1, "cms");
+    /// router.add("/:section", "section");
+    /// router.add_with_priority("/*path", 1, "maintenance");
+    ///
+    /// assert_eq!(*router.recognize("/about").unwrap().handler(), &"maintenance");
+    /// ```
+    pub fn add_with_priority(&mut self, route: &str, priority: i32, dest: T) {
+        let state = self.insert(route);
+        self.nfa.get_mut(state).metadata.as_mut().unwrap().priority = priority;
+        self.endpoint(state).any = Some(dest);
+    }
+
+    /// Add a route to the router that only matches requests with `method`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use route_recognizer::{Method, RecognizeError, Router};
+    ///
+    /// let mut router = Router::new();
+    /// router.add_with_method(Method::GET, "/posts/:id", "show");
+    /// router.add_with_method(Method::DELETE, "/posts/:id", "destroy");
+    ///
+    /// let m = router.recognize_with_method(&Method::GET, "/posts/1").unwrap();
+    /// assert_eq!(*m.handler(), &"show");
+    ///
+    /// let err = router.recognize_with_method(&Method::POST, "/posts/1").unwrap_err();
+    /// let allowed = vec![Method::DELETE, Method::GET];
+    /// assert_eq!(err, RecognizeError::MethodNotAllowed { allowed });
+    /// ```
+    pub fn add_with_method(&mut self, method: Method, route: &str, dest: T) {
+        let state = self.insert(route);
+        self.endpoint(state).methods.insert(method, dest);
+    }
+
+    /// Mount another router under `prefix`.
+    ///
+    /// The routes of `router` are compiled into this router with `prefix`
+    /// prepended, so the prefix may contain params of its own. They end up in
+    /// the same `Params` as the params of the mounted route. Every route
+    /// keeps the `Dots` setting it was added with, and the routes are added
+    /// in the order they were added to `router`. The validators of `router`
+    /// are registered too, unless this router already has a validator with
+    /// the same name. The `TieBreak`, `Ranking`, `EmptyWildcard` and ranker of
+    /// `router` are dropped: the mounted routes follow the settings of this
+    /// router.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use route_recognizer::Router;
+    ///
+    /// let mut posts = Router::new();
+    /// posts.add("/", "index");
+    /// posts.add("/:id", "show");
+    ///
+    /// let mut router = Router::new();
+    /// router.mount("/users/:user_id/posts", posts);
+    ///
+    /// let m = router.recognize("/users/1/posts/2").unwrap();
+    /// assert_eq!(*m.handler(), &"show");
+    /// assert_eq!(m.params().find("user_id"), Some("1"));
+    /// assert_eq!(m.params().find("id"), Some("2"));
+    ///
+    /// let m = router.recognize("/users/1/posts").unwrap();
+    /// assert_eq!(*m.handler(), &"index");
+    /// ```
+    pub fn mount(&mut self, prefix: &str, router: Router<T>) {
+        let prefix = prefix.trim_end_matches('/');
+
+        let nfa = &router.nfa;
+        let mut handlers: Vec<_> = router.handlers.into_iter().collect();
+        handlers.sort_by_key(|&(state, _)| nfa.get(state).metadata.as_ref().unwrap().sequence);
+
+        for (state, endpoint) in handlers {
+            let metadata = nfa.get(state).metadata.as_ref().unwrap();
+            let route = if metadata.route == "/" {
+                prefix.to_string()
+            } else {
+                format!("{}{}", prefix, metadata.route)
+            };
+
+            let priority = metadata.priority;
+            let state = self.insert_with_dots(&route, metadata.dots);
+            self.nfa.get_mut(state).metadata.as_mut().unwrap().priority = priority;
+            let target = self.endpoint(state);
+            if endpoint.any.is_some() {
+                target.any = endpoint.any;
+            }
+            target.methods.extend(endpoint.methods);
+            target.guarded.extend(endpoint.guarded);
+        }
+
+        for (name, validator) in router.validators {
+            self.validators.entry(name).or_insert(validator);
+        }
+    }
+
+    /// Iterate over the registered routes.
+    ///
+    /// Routes are ordered by pattern. A pattern that was added for several
+    /// methods shows up once per method, after its handler for any method.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use route_recognizer::{Method, Router};
+    ///
+    /// let mut router = Router::new();
+    /// router.add("/posts/:id", "show");
+    /// router.add_with_method(Method::DELETE, "/posts/:id", "destroy");
+    ///
+    /// for route in router.routes() {
+    ///     println!("{:?} {} -> {}", route.method(), route.pattern(), route.handler());
+    /// }
+    /// # assert_eq!(router.routes().count(), 2);
+    /// ```
+    pub fn routes(&self) -> Routes<'_, T> {
+        let mut routes = vec![];
+
+        for (&state, endpoint) in &self.handlers {
+            let metadata = self.nfa.get(state).metadata.as_ref().unwrap();
+            if let Some(ref handler) = endpoint.any {
+                routes.push(Route {
+                    method: None,
+                    metadata,
+                    handler,
+                });
+            }
+            for (method, handler) in &endpoint.methods {
+                routes.push(Route {
+                    method: Some(method),
+                    metadata,
+                    handler,
+                });
+            }
+        }
+
+        routes.sort_by(|a, b| a.pattern().cmp(b.pattern()));
+        Routes(routes.into_iter())
+    }
+
+    /// Iterate over the registered handlers mutably, along with their pattern
+    /// and method.
+    ///
+    /// Handlers are yielded in the same order as `routes`.
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        let mut handlers = vec![];
+
+        for (&state, endpoint) in &mut self.handlers {
+            let pattern = &self.nfa.get(state).metadata.as_ref().unwrap().route[..];
+            if let Some(ref mut handler) = endpoint.any {
+                handlers.push((pattern, None, handler));
+            }
+            for (method, handler) in &mut endpoint.methods {
+                handlers.push((pattern, Some(method), handler));
+            }
+        }
+
+        handlers.sort_by(|a, b| a.0.cmp(b.0));
+        IterMut(handlers.into_iter())
+    }
+
+    /// Render the routing automaton as a Graphviz DOT graph.
+    ///
+    /// Acceptance states are labeled with their route and the segment counts
+    /// used to rank it. The output can be rendered with `dot -Tsvg`.
+    pub fn to_dot(&self) -> String {
+        self.nfa.to_dot(|metadata| {
+            format!(
+                "{}\nstatics: {}, dynamics: {}, wildcards: {}",
+                metadata.route, metadata.statics, metadata.dynamics, metadata.wildcards
+            )
+        })
+    }
+
+    fn metadata(&self, state: usize) -> &Metadata {
+        self.nfa.get(state).metadata.as_ref().unwrap()
+    }
+
+    fn rank(&self, state: usize) -> Rank<'_> {
+        self.rank_with(state, &[])
+    }
+
+    fn rank_with<'a>(&'a self, state: usize, captures: &'a [&'a str]) -> Rank<'a> {
+        Rank {
+            metadata: self.metadata(state),
+            captures,
+            ranking: self.ranking,
+            ranker: self.ranker.as_ref().map(|ranker| &*ranker.0),
+            tie_break: self.tie_break,
+        }
+    }
+
+    /// Match `path` against every route whose validators accept it, ordered
+    /// from the winner down.
+    fn candidates<'a>(&self, path: &'a str) -> Vec<nfa::Match<'a>> {
+        let mut matches = self.nfa.process_all(path, |index| self.rank(index));
+        matches.retain(|nfa_match| self.rejection(nfa_match).is_none());
+        if self.ranker.is_some() {
+            matches.sort_by(|first, second| {
+                let first = self.rank_with(first.state, &first.captures);
+                self.rank_with(second.state, &second.captures).cmp(&first)
+            });
+        }
+        matches
+    }
+
+    fn endpoint(&mut self, state: usize) -> &mut Endpoint<T> {
+        self.handlers.entry(state).or_insert_with(Endpoint::new)
+    }
+
+    fn insert(&mut self, route: &str) -> usize {
+        self.insert_with_dots(route, self.dots)
+    }
+
+    fn insert_with_dots(&mut self, mut route: &str, dots: Dots) -> usize {
+        if !route.is_empty() && route.as_bytes()[0] == b'/' {
+            route = &route[1..];
+        }
+
+        let pattern = format!("/{}", route);
+        let state = compile(&mut self.nfa, &pattern, route, &['.', '/'], '/', dots);
+
+        if !self.handlers.contains_key(&state) {
+            self.nfa.get_mut(state).metadata.as_mut().unwrap().sequence = self.sequence;
+            self.sequence += 1;
+        }
+
+        state
+    }
+
+    /// Match a route on the router.
+    ///
+    /// Routes that were only added for specific methods are skipped.
+    pub fn recognize(&self, route: &str) -> Result<Match<&T>, String> {
+        let mut path = route;
+        if !path.is_empty() && path.as_bytes()[0] == b'/' {
+            path = &path[1..];
+        }
+
+        let nfa = &self.nfa;
+        let result = if self.ranker.is_some() {
+            self.candidates(path)
+                .into_iter()
+                .next()
+                .ok_or_else(|| format!("No route matches {}", path))
+        } else {
+            nfa.process(path, |index| self.rank(index))
+        };
+
+        match result {
+            Ok(nfa_match) => match self.handlers[&nfa_match.state].any {
+                Some(ref handler) if self.rejection(&nfa_match).is_none() => {
+                    Ok(Match::new(handler, self.params(&nfa_match)))
+                }
+                _ => self
+                    .recognize_all(route)
+                    .into_iter()
+                    .next()
+                    .ok_or_else(|| format!("No route handles {} for any method", path)),
+            },
+            Err(str) => Err(str),
+        }
+    }
+
+    /// Match a route on the router, returning every route that matches.
+    ///
+    /// Matches are ordered by priority, so the first entry is the one
+    /// `recognize` would have returned. Each match carries its own params.
+    pub fn recognize_all(&self, mut path: &str) -> Vec<Match<&T>> {
+        if !path.is_empty() && path.as_bytes()[0] == b'/' {
+            path = &path[1..];
+        }
+
+        self.candidates(path)
+            .into_iter()
+            .filter_map(|nfa_match| {
+                let handler = self.handlers[&nfa_match.state].any.as_ref()?;
+                Some(Match::new(handler, self.params(&nfa_match)))
+            })
+            .collect()
+    }
+
+    /// Match the longest route that is a prefix of the path.
+    ///
+    /// The prefix has to end at a segment boundary, so `/static` matches
+    /// `/static/css/main.css` but not `/statics`. Among the routes that match
+    /// the longest prefix, the usual priority rules apply.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use route_recognizer::Router;
+    ///
+    /// let mut router = Router::new();
+    /// router.add("/static", "files");
+    /// router.add("/apps/:app", "app");
+    ///
+    /// let m = router.recognize_prefix("/apps/blog/posts/1").unwrap();
+    /// assert_eq!(*m.handler(), &"app");
+    /// assert_eq!(m.params().find("app"), Some("blog"));
+    /// assert_eq!(m.prefix(), "/apps/blog");
+    /// assert_eq!(m.rest(), "/posts/1");
+    /// ```
+    pub fn recognize_prefix<'a>(&self, route: &'a str) -> Result<PrefixMatch<'a, &T>, String> {
+        let mut path = route;
+        if !path.is_empty() && path.as_bytes()[0] == b'/' {
+            path = &path[1..];
+        }
+
+        let nfa = &self.nfa;
+        let result = nfa.process_prefix(path, '/', |nfa_match| {
+            self.handlers[&nfa_match.state].any.as_ref()?;
+            if self.rejection(nfa_match).is_some() {
+                return None;
+            }
+            Some(self.rank(nfa_match.state))
+        });
+
+        match result {
+            Some((nfa_match, len)) => {
+                let handler = self.handlers[&nfa_match.state].any.as_ref().unwrap();
+                let split = if len == 0 {
+                    0
+                } else {
+                    route.len() - path.len() + len
+                };
+
+                Ok(PrefixMatch {
+                    handler,
+                    params: self.params(&nfa_match),
+                    prefix: &route[..split],
+                    rest: &route[split..],
+                })
+            }
+            None => Err(format!("No route matches a prefix of {}", route)),
+        }
+    }
+
+    /// Match a route on the router for a request with `method`.
+    ///
+    /// Routes are tried in priority order, and the first one with a handler
+    /// for `method`, or a handler for any method, wins. If routes match the
+    /// path but none of them handles `method`, the methods they do handle are
+    /// returned in `RecognizeError::MethodNotAllowed`. Routes that only have
+    /// guarded handlers are not considered.
+    pub fn recognize_with_method(
+        &self,
+        method: &Method,
+        mut path: &str,
+    ) -> Result<Match<&T>, RecognizeError> {
+        if !path.is_empty() && path.as_bytes()[0] == b'/' {
+            path = &path[1..];
+        }
+
+        let mut allowed = Vec::new();
+        for nfa_match in &self.candidates(path) {
+            let endpoint = &self.handlers[&nfa_match.state];
+            if let Some(handler) = endpoint.get(method) {
+                return Ok(Match::new(handler, self.params(nfa_match)));
+            }
+            allowed.extend(endpoint.methods.keys().cloned());
+        }
+
+        if allowed.is_empty() {
+            return Err(RecognizeError::NotFound);
+        }
+        allowed.sort();
+        allowed.dedup();
+        Err(RecognizeError::MethodNotAllowed { allowed })
+    }
+
+    fn params(&self, nfa_match: &nfa::Match<'_>) -> Params {
+        let mut map = Params::new();
+        let state = &self.nfa.get(nfa_match.state);
+        let metadata = state.metadata.as_ref().unwrap();
+        let param_names = metadata.param_names.clone();
+
+        for (i, name) in param_names.iter().enumerate() {
+            // An optional wildcard at the end that matched nothing has no
+            // capture.
+            let capture = match nfa_match.captures.get(i) {
+                Some(capture) => capture,
+                None if self.empty_wildcard == EmptyWildcard::Absent => break,
+                None => "",
+            };
+            if name.is_empty() {
+                map.push(capture.to_string());
+            } else {
+                map.insert(name.to_string(), capture.to_string());
+            }
+        }
+
+        map
+    }
+}
+
+impl<T> Default for Router<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Compile `route` into `nfa`, returning its acceptance state.
+///
+/// Dynamic segments match any run of characters up to the next `boundary`,
+/// and up to the next dot too if `dots` excludes them or the param ends in
+/// `[^.]`. The `pattern` is recorded as the route's source in its `Metadata`.
+fn compile(
+    nfa: &mut NFA<Metadata>,
+    pattern: &str,
+    route: &str,
+    separators: &[char],
+    boundary: char,
+    dots: Dots,
+) -> usize {
+    // The states the route can be in after the segments compiled so far.
+    let mut states = vec![0];
+    let mut metadata = Metadata::new(pattern);
+    metadata.dots = dots;
+
+    let segments = segments(route, separators);
+    let last = segments.len().saturating_sub(1);
+
+    for (i, (separator, segment)) in segments.into_iter().enumerate() {
+        let previous = states.clone();
+        let mut state = match separator {
+            Some(separator) => join(nfa, &states, CharacterClass::valid_char(separator)),
+            None => states[0],
+        };
+
+        if !segment.is_empty() && segment.as_bytes()[0] == b':' {
+            let (name, constraints) = split_constraints(&segment[1..], false);
+            let Constraints {
+                validator,
+                choices,
+                bounds,
+                no_dots,
+                ..
+            } = constraints;
+            let excluded = match dots {
+                Dots::Excluded => format!("{}.", boundary),
+                Dots::Greedy if no_dots => format!("{}.", boundary),
+                Dots::Greedy => boundary.to_string(),
+            };
+            if choices.as_ref().is_some_and(Vec::is_empty) {
+                panic!("{}: a param needs at least one choice", pattern);
+            }
+            if bounds.is_some_and(|(min, _)| min == 0) {
+                panic!("{}: a param must match at least one character", pattern);
+            }
+            match (&choices, bounds) {
+                (Some(choices), _) => {
+                    states = process_choice_segment(nfa, state, choices);
+                }
+                (_, Some((min, max))) => {
+                    states = process_bounded_segment(nfa, state, &excluded, min, max, validator);
+                }
+                _ => {
+                    state = process_dynamic_segment(nfa, state, &excluded, validator);
+                    states = vec![state];
+                }
+            }
+            metadata.dynamics += 1;
+            metadata.kinds.push(SegmentKind::Dynamic);
+            metadata.param_names.push(name.to_string());
+            metadata.validators.push(validator.map(str::to_string));
+            metadata.choices.push(choices.unwrap_or_default());
+        } else if !segment.is_empty() && segment.as_bytes()[0] == b'*' {
+            let (name, constraints) = split_constraints(&segment[1..], true);
+            let Constraints {
+                validator,
+                bounds,
+                optional,
+                ..
+            } = constraints;
+            states = match bounds {
+                Some((min, max)) => {
+                    process_bounded_star(nfa, state, boundary, min.max(1), max, validator)
+                }
+                None => vec![process_star_state(nfa, state, validator)],
+            };
+            // Only a wildcard at the end of the route can match nothing, so
+            // that the captures of the other params keep their positions.
+            let optional = optional || bounds.is_some_and(|(min, _)| min == 0);
+            if optional && i != last {
+                panic!(
+                    "{}: only the last segment of a route can be an optional wildcard",
+                    pattern
+                );
+            }
+            if optional {
+                states.push(state);
+                if separator.is_some() {
+                    states.extend(previous);
+                }
+            }
+            metadata.wildcards += 1;
+            metadata.kinds.push(SegmentKind::Wildcard);
+            metadata.param_names.push(name.to_string());
+            metadata.validators.push(validator.map(str::to_string));
+            metadata.choices.push(Vec::new());
+        } else {
+            state = process_static_segment(segment, nfa, state);
+            states = vec![state];
+            metadata.statics += 1;
+            metadata.kinds.push(SegmentKind::Static);
+        }
+    }
+
+    let state = states[0];
+    // Adding a route again keeps its priority and its place in the order of
+    // insertion, but not the states the route could end in before.
+    if let Some(ref existing) = nfa.get(state).metadata {
+        metadata.priority = existing.priority;
+        metadata.sequence = existing.sequence;
+        nfa.drop_aliases(state);
+    }
+
+    nfa.acceptance(state);
+    nfa.metadata(state, metadata);
+    nfa.unalias(state);
+    for &alias in &states[1..] {
+        // A state that another route ends in keeps matching that route.
+        if nfa.get(alias).metadata.is_none() {
+            nfa.alias(alias, state);
+        }
+    }
+    state
+}
+
+/// Add a state matching `chars` after every state in `states`.
+///
+/// When there are several states, the new state is only shared with routes
+/// that continue from the same states.
+fn join<T>(nfa: &mut NFA<T>, states: &[usize], chars: CharacterClass) -> usize {
+    if let [state] = *states {
+        return nfa.put(state, chars);
+    }
+
+    let state = nfa.put_tagged(states[0], chars, Some(&format!("{:?}", states)));
+    for &other in &states[1..] {
+        nfa.put_state(other, state);
+    }
+    state
+}
 
 fn process_static_segment<T>(segment: &str, nfa: &mut NFA<T>, mut state: usize) -> usize {
     for char in segment.chars() {
@@ -328,8 +1598,130 @@ fn process_static_segment<T>(segment: &str, nfa: &mut NFA<T>, mut state: usize)
     state
 }
 
-fn process_dynamic_segment<T>(nfa: &mut NFA<T>, mut state: usize) -> usize {
-    state = nfa.put(state, CharacterClass::invalid_char('/'));
+/// The constraints that may follow the name of a param or wildcard.
+#[derive(Default)]
+struct Constraints<'a> {
+    validator: Option<&'a str>,
+    choices: Option<Vec<String>>,
+    bounds: Option<(usize, Option<usize>)>,
+    no_dots: bool,
+    optional: bool,
+}
+
+/// Split a param like `name{1,4}[^.]<v>` into its name and constraints, which
+/// may come in any order. Params accept validators, choices, bounds and
+/// `[^.]`, and wildcards accept validators, bounds and `?`.
+fn split_constraints(param: &str, wildcard: bool) -> (&str, Constraints<'_>) {
+    let mut name = param;
+    let mut constraints = Constraints::default();
+
+    loop {
+        if constraints.validator.is_none() {
+            if let (rest, Some(validator)) = split_validator(name) {
+                name = rest;
+                constraints.validator = Some(validator);
+                continue;
+            }
+        }
+        if constraints.bounds.is_none() {
+            if let (rest, Some(bounds)) = split_bounds(name) {
+                name = rest;
+                constraints.bounds = Some(bounds);
+                continue;
+            }
+        }
+        if wildcard && !constraints.optional {
+            if let Some(rest) = name.strip_suffix('?') {
+                name = rest;
+                constraints.optional = true;
+                continue;
+            }
+        }
+        if !wildcard && !constraints.no_dots {
+            if let Some(rest) = name.strip_suffix("[^.]") {
+                name = rest;
+                constraints.no_dots = true;
+                continue;
+            }
+        }
+        if !wildcard && constraints.choices.is_none() {
+            if let (rest, Some(choices)) = split_choices(name) {
+                name = rest;
+                constraints.choices = Some(choices);
+                continue;
+            }
+        }
+        return (name, constraints);
+    }
+}
+
+/// Split a param like `format(json|xml)` into its name and allowed values.
+fn split_choices(param: &str) -> (&str, Option<Vec<String>>) {
+    match param.rfind('(') {
+        Some(i) if param.ends_with(')') => {
+            let choices = param[i + 1..param.len() - 1]
+                .split('|')
+                .filter(|choice| !choice.is_empty())
+                .map(str::to_string)
+                .collect();
+            (&param[..i], Some(choices))
+        }
+        _ => (param, None),
+    }
+}
+
+/// Split a param like `code{2,3}` into its name and the least and most
+/// characters it may capture, or segments for a wildcard. `{n}`, `{min,}`,
+/// `{,max}` and `{..max}` are accepted too, with a minimum of `1` by default.
+fn split_bounds(param: &str) -> (&str, Option<(usize, Option<usize>)>) {
+    let (name, bounds) = match param.rfind('{') {
+        Some(i) if param.ends_with('}') => (&param[..i], &param[i + 1..param.len() - 1]),
+        _ => return (param, None),
+    };
+
+    let parse = |bound: &str| -> Option<Option<usize>> {
+        match bound.trim() {
+            "" => Some(None),
+            bound => bound.parse().ok().map(Some),
+        }
+    };
+    let (min, max) = match bounds
+        .find(',')
+        .map(|i| (i, 1))
+        .or(bounds.find("..").map(|i| (i, 2)))
+    {
+        Some((i, len)) => (parse(&bounds[..i]), parse(&bounds[i + len..])),
+        None => (parse(bounds), parse(bounds)),
+    };
+
+    match (min, max) {
+        (Some(min), Some(max)) => {
+            let min = min.unwrap_or(1);
+            match max {
+                Some(max) if max < min || max == 0 => (param, None),
+                max => (name, Some((min, max))),
+            }
+        }
+        _ => (param, None),
+    }
+}
+
+/// Split a param like `id<uuid>` into its name and the name of its validator.
+fn split_validator(param: &str) -> (&str, Option<&str>) {
+    match param.rfind('<') {
+        Some(i) if param.ends_with('>') => (&param[..i], Some(&param[i + 1..param.len() - 1])),
+        _ => (param, None),
+    }
+}
+
+fn process_dynamic_segment<T>(
+    nfa: &mut NFA<T>,
+    mut state: usize,
+    excluded: &str,
+    validator: Option<&str>,
+) -> usize {
+    let tag = validator.map(|validator| format!("<{}>", validator));
+    state = nfa.put_tagged(state, CharacterClass::invalid(excluded), tag.as_deref());
     nfa.put_state(state, state);
     nfa.start_capture(state);
     nfa.end_capture(state);
@@ -337,8 +1729,124 @@ fn process_dynamic_segment<T>(nfa: &mut NFA<T>, mut state: usize) -> usize {
     state
 }
 
-fn process_star_state<T>(nfa: &mut NFA<T>, mut state: usize) -> usize {
-    state = nfa.put(state, CharacterClass::any());
+/// Add a captured branch of static states for every choice, returning the
+/// last state of each branch.
+fn process_choice_segment<T>(nfa: &mut NFA<T>, state: usize, choices: &[String]) -> Vec<usize> {
+    let mut ends = vec![];
+
+    for choice in choices {
+        // Branches of different choices never share states, so that a capture
+        // ends after the whole choice even if another choice extends it.
+        let tag = format!("({})={}", choices.join("|"), choice);
+        let mut current = state;
+        for (i, char) in choice.chars().enumerate() {
+            current = nfa.put_tagged(current, CharacterClass::valid_char(char), Some(&tag));
+            if i == 0 {
+                nfa.start_capture(current);
+            }
+        }
+        nfa.end_capture(current);
+        ends.push(current);
+    }
+
+    ends
+}
+
+/// Add a param that captures between `min` and `max` characters, returning
+/// the states the param can end in.
+fn process_bounded_segment<T>(
+    nfa: &mut NFA<T>,
+    state: usize,
+    excluded: &str,
+    min: usize,
+    max: Option<usize>,
+    validator: Option<&str>,
+) -> Vec<usize> {
+    let mut tag = match max {
+        Some(max) => format!("{{{},{}}}", min, max),
+        None => format!("{{{},}}", min),
+    };
+    // Chains that also exclude dots must not be shared with those that don't.
+    if excluded.len() > 1 {
+        tag.push_str("[^.]");
+    }
+    if let Some(validator) = validator {
+        tag.push_str(&format!("<{}>", validator));
+    }
+
+    let classes = vec![CharacterClass::invalid(excluded); max.unwrap_or(min)];
+    let chain = nfa.put_chain(state, &classes, &tag);
+    nfa.start_capture(chain[0]);
+    for &state in &chain[min - 1..] {
+        nfa.end_capture(state);
+    }
+    if max.is_none() {
+        nfa.put_state(chain[min - 1], chain[min - 1]);
+    }
+
+    chain[min - 1..].to_vec()
+}
+
+/// Add a wildcard that captures between `min` and `max` segments separated
+/// by `boundary`, returning the states the wildcard can end in.
+fn process_bounded_star<T>(
+    nfa: &mut NFA<T>,
+    state: usize,
+    boundary: char,
+    min: usize,
+    max: Option<usize>,
+    validator: Option<&str>,
+) -> Vec<usize> {
+    let mut tag = match max {
+        Some(max) => format!("*{{{},{}}}", min, max),
+        None => format!("*{{{},}}", min),
+    };
+    if let Some(validator) = validator {
+        tag.push_str(&format!("<{}>", validator));
+    }
+
+    // Every segment is a looping state, and segments are joined by a state
+    // for the boundary. Without a maximum, the last boundary loops back.
+    let count = max.unwrap_or(min);
+    let mut classes = vec![];
+    for i in 0..count {
+        if i > 0 {
+            classes.push(CharacterClass::valid_char(boundary));
+        }
+        classes.push(CharacterClass::invalid_char(boundary));
+    }
+    if max.is_none() {
+        classes.push(CharacterClass::valid_char(boundary));
+    }
+
+    let chain = nfa.put_chain(state, &classes, &tag);
+    let segments: Vec<usize> = chain.iter().cloned().step_by(2).take(count).collect();
+    for &segment in &segments {
+        nfa.put_state(segment, segment);
+    }
+    if max.is_none() {
+        nfa.put_state(chain[chain.len() - 1], segments[count - 1]);
+    }
+
+    nfa.start_capture(segments[0]);
+    for &segment in &segments[min - 1..] {
+        nfa.end_capture(segment);
+    }
+    segments[min - 1..].to_vec()
+}
+
+/// Get the choice that a branch added by `process_choice_segment` starts.
+fn choice_of<T>(state: &nfa::State<T>) -> Option<&str> {
+    let tag = state.tag.as_deref()?;
+    if !state.start_capture || !tag.starts_with('(') {
+        return None;
+    }
+    tag.find(")=").map(|i| &tag[i + 2..])
+}
+
+fn process_star_state<T>(nfa: &mut NFA<T>, mut state: usize, validator: Option<&str>) -> usize {
+    let tag = validator.map(|validator| format!("<{}>", validator));
+    state = nfa.put_tagged(state, CharacterClass::any(), tag.as_deref());
     nfa.put_state(state, state);
     nfa.start_capture(state);
     nfa.end_capture(state);
@@ -348,7 +1856,10 @@ fn process_star_state<T>(nfa: &mut NFA<T>, mut state: usize) -> usize {
 
 #[cfg(test)]
 mod tests {
-    use super::{Params, Router};
+    use super::{
+        Dots, EmptyWildcard, Method, Outcome, Params, Ranking, RecognizeError, RouteInfo, Router,
+        SegmentKind, TieBreak,
+    };
 
     #[test]
     fn basic_router() {
@@ -503,10 +2014,761 @@ mod tests {
         let m = router.recognize("/foo/test/bar").unwrap();
         assert_eq!(*m.handler, "test");
         assert_eq!(m.params, Params::new());
+        assert_eq!(m.params.iter().count(), 0);
+        assert_eq!(m.params.positional(0), Some("test"));
 
         let m = router.recognize("/foo/test/blah").unwrap();
         assert_eq!(*m.handler, "test2");
-        assert_eq!(m.params, params("bar", "test"));
+        assert_eq!(m.params.find("bar"), Some("test"));
+        assert_eq!(m.params.positional(0), Some("test"));
+        assert_eq!(m.params.positional(1), Some("blah"));
+        assert_eq!(m.params.positional(2), None);
+    }
+
+    #[test]
+    fn repeated_params() {
+        let mut router = Router::new();
+        router.add("/a/:id/b/:id/*", "ids");
+
+        let m = router.recognize("/a/1/b/2/c").unwrap();
+        assert_eq!(m.params.get_all("id"), vec!["1", "2"]);
+        assert_eq!(m.params.find("id"), Some("2"));
+        assert_eq!(m.params["id"], "2");
+        assert_eq!(m.params.get_all(""), Vec::<&str>::new());
+        assert_eq!(
+            m.params.iter().collect::<Vec<_>>(),
+            vec![("id", "1"), ("id", "2")]
+        );
+        assert_eq!(m.params.positional(2), Some("c"));
+    }
+
+    #[test]
+    #[should_panic(expected = "params[] did not exist")]
+    fn unnamed_index() {
+        let mut router = Router::new();
+        router.add("/a/:", "unnamed");
+        let _ = &router.recognize("/a/1").unwrap().params[""];
+    }
+
+    #[test]
+    fn ordered_params() {
+        let mut router = Router::new();
+        router.add("/:year/:month/:day/*slug", "post");
+
+        let m = router.recognize("/2024/05/17/hello/world").unwrap();
+        assert_eq!(
+            m.params.iter().collect::<Vec<_>>(),
+            vec![
+                ("year", "2024"),
+                ("month", "05"),
+                ("day", "17"),
+                ("slug", "hello/world")
+            ]
+        );
+        assert_eq!(m.params.len(), 4);
+        assert_eq!(m.params.get(2), Some(("day", "17")));
+        assert_eq!(m.params.get(4), None);
+        assert_eq!(m.params[3], "hello/world");
+
+        let mut reversed = Params::new();
+        reversed.insert("month".to_string(), "05".to_string());
+        reversed.insert("year".to_string(), "2024".to_string());
+        assert_ne!(reversed, two_params("year", "2024", "month", "05"));
+        assert!(Params::new().is_empty());
+    }
+
+    #[test]
+    fn enumerated_params() {
+        let mut router = Router::new();
+        router.add("/report.:format(json|jsonp|csv)", "report");
+        router.add("/report.:format(json|jsonp|csv)/raw", "raw");
+        router.add("/report.json", "static");
+        router.add("/:name", "name");
+
+        let m = router.recognize("/report.jsonp").unwrap();
+        assert_eq!(*m.handler, "report");
+        assert_eq!(m.params, params("format", "jsonp"));
+
+        let m = router.recognize("/report.csv/raw").unwrap();
+        assert_eq!(*m.handler, "raw");
+        assert_eq!(m.params, params("format", "csv"));
+
+        assert_eq!(*router.recognize("/report.json").unwrap().handler, "static");
+        assert_eq!(*router.recognize("/report").unwrap().handler, "name");
+        assert_eq!(*router.recognize("/report.xml").unwrap().handler, "name");
+        assert_eq!(*router.recognize("/report.cs").unwrap().handler, "name");
+        assert!(router.recognize("/report.json/").is_err());
+
+        let route = router.routes().find(|r| *r.handler() == "report").unwrap();
+        assert_eq!(
+            route.param_values("format"),
+            Some(vec!["json", "jsonp", "csv"])
+        );
+        assert_eq!(route.param_names(), vec!["format"]);
+        let route = router.routes().find(|r| *r.handler() == "name").unwrap();
+        assert_eq!(route.param_values("name"), None);
+
+        let explanation = router.explain("/report.xml");
+        let report = explanation
+            .candidates()
+            .iter()
+            .find(|c| c.pattern() == "/report.:format(json|jsonp|csv)")
+            .unwrap();
+        assert_eq!(
+            *report.outcome(),
+            Outcome::Failed {
+                offset: 8,
+                expected: vec!["csv".to_string(), "json".to_string(), "jsonp".to_string()],
+            }
+        );
+        assert!(router.lint().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "/a/*b?/c: only the last segment")]
+    fn optional_wildcard_in_the_middle() {
+        let mut router = Router::new();
+        router.add("/a/*b?/c", ());
+    }
+
+    #[test]
+    #[should_panic(expected = "/a/*b{0,2}/c: only the last segment")]
+    fn empty_bounded_wildcard_in_the_middle() {
+        let mut router = Router::new();
+        router.add("/a/*b{0,2}/c", ());
+    }
+
+    #[test]
+    #[should_panic(expected = "/:c{0,3}: a param must match at least one character")]
+    fn empty_bounded_param() {
+        let mut router = Router::new();
+        router.add("/:c{0,3}", ());
+    }
+
+    #[test]
+    #[should_panic(expected = "/report.:f(): a param needs at least one choice")]
+    fn empty_choices() {
+        let mut router = Router::new();
+        router.add("/report.:f()", ());
+    }
+
+    #[test]
+    fn unbalanced_constraints() {
+        let mut router = Router::new();
+        router.add("/:x}/y", "x");
+        router.add("/:a(b/c", "a");
+        router.add("/:n{1,2/d", "n");
+
+        let m = router.recognize("/1/y").unwrap();
+        assert_eq!(*m.handler, "x");
+        assert_eq!(m.params, params("x}", "1"));
+
+        let m = router.recognize("/1/c").unwrap();
+        assert_eq!(*m.handler, "a");
+        assert_eq!(m.params, params("a(b", "1"));
+
+        let m = router.recognize("/123/d").unwrap();
+        assert_eq!(*m.handler, "n");
+        assert_eq!(m.params, params("n{1,2", "123"));
+
+        assert!(router.recognize("/1/y/c").is_err());
+    }
+
+    #[test]
+    fn bounded_params() {
+        let mut router = Router::new();
+        router.add("/:lang{2,3}", "lang");
+        router.add("/:lang{2,3}/about", "about");
+        router.add("/:slug", "slug");
+        router.add("/posts/:id{..4}.:format{3}", "post");
+        router.add("/users/:name{3,}", "user");
+
+        assert_eq!(*router.recognize("/en").unwrap().handler, "lang");
+        assert_eq!(
+            router.recognize("/deu").unwrap().params,
+            params("lang", "deu")
+        );
+        assert_eq!(*router.recognize("/e").unwrap().handler, "slug");
+        assert_eq!(*router.recognize("/hello").unwrap().handler, "slug");
+        assert_eq!(*router.recognize("/pt/about").unwrap().handler, "about");
+        assert!(router.recognize("/port/about").is_err());
+
+        let m = router.recognize("/posts/12.txt").unwrap();
+        assert_eq!(*m.handler, "post");
+        assert_eq!(m.params, two_params("id", "12", "format", "txt"));
+        assert!(router.recognize("/posts/12345.txt").is_err());
+        assert!(router.recognize("/posts/1.json").is_err());
+
+        assert_eq!(
+            router.recognize("/users/alice").unwrap().params,
+            params("name", "alice")
+        );
+        assert!(router.recognize("/users/al").is_err());
+
+        let route = router.routes().find(|r| *r.handler() == "post").unwrap();
+        assert_eq!(route.param_names(), vec!["id", "format"]);
+        assert!(router.lint().is_empty());
+    }
+
+    #[test]
+    fn bounded_wildcards() {
+        let mut router = Router::new();
+        router.add("/docs/*path{1,3}", "docs");
+        router.add("/docs/*path{2,}/edit", "edit");
+        router.add("/assets/*path?", "assets");
+        router.add("/assets/", "root");
+        router.add("/*path", "fallback");
+
+        let m = router.recognize("/docs/a/b/c").unwrap();
+        assert_eq!(*m.handler, "docs");
+        assert_eq!(m.params, params("path", "a/b/c"));
+        assert_eq!(m.params.segments("path"), Some(vec!["a", "b", "c"]));
+        assert_eq!(*router.recognize("/docs/a").unwrap().handler, "docs");
+        assert_eq!(
+            *router.recognize("/docs/a/b/c/d").unwrap().handler,
+            "fallback"
+        );
+        assert_eq!(*router.recognize("/docs/a//b").unwrap().handler, "fallback");
+
+        let m = router.recognize("/docs/a/b/c/d/edit").unwrap();
+        assert_eq!(*m.handler, "edit");
+        assert_eq!(m.params, params("path", "a/b/c/d"));
+        assert_eq!(*router.recognize("/docs/a/edit").unwrap().handler, "docs");
+
+        let m = router.recognize("/assets/css/main.css").unwrap();
+        assert_eq!(*m.handler, "assets");
+        assert_eq!(m.params, params("path", "css/main.css"));
+        assert_eq!(*router.recognize("/assets/").unwrap().handler, "root");
+        assert_eq!(*router.recognize("/assets").unwrap().handler, "assets");
+
+        let mut router = Router::new();
+        router.add("/assets/*path?", "assets");
+        router.add("/*all", "fallback");
+        let m = router.recognize("/assets/").unwrap();
+        assert_eq!(*m.handler, "assets");
+        assert_eq!(m.params, params("path", ""));
+        assert_eq!(m.params.segments("path"), Some(vec![]));
+        assert_eq!(
+            router.explain("/assets/").winner().unwrap().pattern(),
+            "/assets/*path?"
+        );
+    }
+
+    #[test]
+    fn excluded_dots() {
+        let mut router = Router::new();
+        router.add("/last/:name.:ext", "last");
+        router.add("/first/:name[^.].:ext", "first");
+        router.add("/short/:name{1,4}[^.]", "short");
+        router.set_dots(Dots::Excluded);
+        router.add("/none/:name.:ext", "none");
+
+        let m = router.recognize("/last/archive.tar.gz").unwrap();
+        assert_eq!(m.params().find("name"), Some("archive.tar"));
+        assert_eq!(m.params().find("ext"), Some("gz"));
+
+        let m = router.recognize("/first/archive.tar.gz").unwrap();
+        assert_eq!(m.params().find("name"), Some("archive"));
+        assert_eq!(m.params().find("ext"), Some("tar.gz"));
+        assert_eq!(
+            router
+                .routes()
+                .find(|r| *r.handler() == "first")
+                .unwrap()
+                .param_names(),
+            vec!["name", "ext"]
+        );
+
+        assert!(router.recognize("/short/a.b").is_err());
+        assert!(router.recognize("/short/abcd").is_ok());
+
+        let m = router.recognize("/none/archive.gz").unwrap();
+        assert_eq!(m.params().find("name"), Some("archive"));
+        assert!(router.recognize("/none/archive.tar.gz").is_err());
+    }
+
+    #[test]
+    fn constraint_order() {
+        let mut router = Router::new();
+        router.add_validator("lower", |value: &str| {
+            value.bytes().all(|b| b.is_ascii_lowercase())
+        });
+        router.add("/a/:name[^.]{1,4}.:ext", "a");
+        router.add("/b/:name<lower>[^.].:ext", "b");
+        router.add("/c/*path<lower>?", "c");
+
+        let m = router.recognize("/a/ab.tar.gz").unwrap();
+        assert_eq!(*m.handler(), &"a");
+        assert_eq!(m.params().find("name"), Some("ab"));
+        assert!(router.recognize("/a/abcde.gz").is_err());
+
+        let m = router.recognize("/b/ab.tar.gz").unwrap();
+        assert_eq!(m.params().find("name"), Some("ab"));
+        assert!(router.recognize("/b/AB.gz").is_err());
+
+        assert_eq!(
+            router.recognize("/c").unwrap().params().find("path"),
+            Some("")
+        );
+        assert!(router.recognize("/c/ab").is_ok());
+        assert!(router.recognize("/c/AB").is_err());
+
+        let names: Vec<_> = router.routes().map(|r| r.param_names()).collect();
+        assert_eq!(
+            names,
+            vec![vec!["name", "ext"], vec!["name", "ext"], vec!["path"]]
+        );
+    }
+
+    #[test]
+    fn mounted_dots() {
+        let mut files = Router::new();
+        files.set_dots(Dots::Excluded);
+        files.add("/:name", "file");
+        assert!(files.recognize("/a.b").is_err());
+
+        let mut router = Router::new();
+        router.mount("/x", files);
+        router.add("/y/:name", "greedy");
+        assert!(router.recognize("/x/a.b").is_err());
+        assert!(router.recognize("/x/a").is_ok());
+        assert!(router.recognize("/y/a.b").is_ok());
+    }
+
+    #[test]
+    fn mounted_order() {
+        let mut sub = Router::new();
+        sub.add("/a/:y/z", "long");
+        sub.add("/:x/b", "first");
+        sub.add("/a/:y", "last");
+        assert_eq!(*sub.recognize("/a/b").unwrap().handler, "first");
+
+        let mut router = Router::new();
+        router.mount("/m", sub);
+        assert_eq!(*router.recognize("/m/a/b").unwrap().handler, "first");
+    }
+
+    #[test]
+    fn empty_wildcards() {
+        let mut router = Router::new();
+        router.add("/", "home");
+        router.add("/*path?", "spa");
+        router.add("/api/*rest?", "api");
+        router.add("/files/*", "files");
+
+        assert_eq!(*router.recognize("/").unwrap().handler, "home");
+        let m = router.recognize("/settings/profile").unwrap();
+        assert_eq!(*m.handler, "spa");
+        assert_eq!(m.params, params("path", "settings/profile"));
+
+        for path in &["/api", "/api/"] {
+            let m = router.recognize(path).unwrap();
+            assert_eq!(*m.handler, "api");
+            assert_eq!(m.params, params("rest", ""));
+        }
+        assert_eq!(*router.recognize("/files").unwrap().handler, "spa");
+
+        router.set_empty_wildcard(EmptyWildcard::Absent);
+        let m = router.recognize("/api").unwrap();
+        assert_eq!(*m.handler, "api");
+        assert_eq!(m.params, Params::new());
+        assert_eq!(
+            router.recognize("/api/v1").unwrap().params,
+            params("rest", "v1")
+        );
+
+        let mut router = Router::new();
+        router.add("/*path?", "spa");
+        let m = router.recognize("/").unwrap();
+        assert_eq!(*m.handler, "spa");
+        assert_eq!(m.params, params("path", ""));
+    }
+
+    #[test]
+    fn readded_wildcards() {
+        let mut router = Router::new();
+        router.add("/a/*x?", "opt");
+        router.add("/a/*y", "req");
+        assert!(router.recognize("/a").is_err());
+        assert_eq!(router.recognize("/a/b").unwrap().params, params("y", "b"));
+
+        let mut router = Router::new();
+        router.add("/a/*x{0,2}", "opt");
+        router.add("/a/*y{1,2}", "req");
+        assert!(router.recognize("/a").is_err());
+        assert_eq!(*router.recognize("/a/b/c").unwrap().handler, "req");
+
+        let mut router = Router::new();
+        router.add("/a/*x", "req");
+        router.add("/a/*y?", "opt");
+        let m = router.recognize("/a").unwrap();
+        assert_eq!(*m.handler, "opt");
+        assert_eq!(m.params, params("y", ""));
+    }
+
+    #[test]
+    fn recognize_all() {
+        let mut router = Router::new();
+
+        router.add("/posts/:id", "id".to_string());
+        router.add("/posts/new", "new".to_string());
+        router.add("/*path", "path".to_string());
+        router.add("/comments", "comments".to_string());
+
+        let all = router.recognize_all("/posts/new");
+        let handlers: Vec<&str> = all.iter().map(|m| m.handler().as_str()).collect();
+        assert_eq!(handlers, vec!["new", "id", "path"]);
+        assert_eq!(all[0].params, Params::new());
+        assert_eq!(all[1].params, params("id", "new"));
+        assert_eq!(all[2].params, params("path", "posts/new"));
+
+        let all = router.recognize_all("/posts/1/edit");
+        assert_eq!(all.len(), 1);
+        assert_eq!(*all[0].handler, "path".to_string());
+
+        assert!(router.recognize_all("/").is_empty());
+    }
+
+    #[test]
+    fn methods() {
+        let mut router = Router::new();
+
+        router.add_with_method(Method::GET, "/posts/new", "new");
+        router.add_with_method(Method::GET, "/posts/:id", "show");
+        router.add_with_method(Method::DELETE, "/posts/:id", "destroy");
+        router.add("/health", "health");
+
+        let m = router
+            .recognize_with_method(&Method::GET, "/posts/new")
+            .unwrap();
+        assert_eq!(*m.handler, "new");
+
+        let m = router
+            .recognize_with_method(&Method::DELETE, "/posts/new")
+            .unwrap();
+        assert_eq!(*m.handler, "destroy");
+        assert_eq!(m.params, params("id", "new"));
+
+        let m = router
+            .recognize_with_method(&Method::PUT, "/health")
+            .unwrap();
+        assert_eq!(*m.handler, "health");
+
+        let err = router.recognize_with_method(&Method::POST, "/posts/new");
+        let allowed = vec![Method::DELETE, Method::GET];
+        assert_eq!(
+            err.unwrap_err(),
+            RecognizeError::MethodNotAllowed { allowed }
+        );
+
+        let err = router.recognize_with_method(&Method::GET, "/comments");
+        assert_eq!(err.unwrap_err(), RecognizeError::NotFound);
+
+        assert!(router.recognize("/posts/new").is_err());
+        assert_eq!(*router.recognize("/health").unwrap().handler, "health");
+    }
+
+    #[test]
+    fn methods_fall_back_to_any() {
+        let mut router = Router::new();
+
+        router.add("/posts/:id", "any");
+        router.add_with_method(Method::GET, "/posts/new", "new");
+
+        let m = router.recognize("/posts/new").unwrap();
+        assert_eq!(*m.handler, "any");
+        assert_eq!(m.params, params("id", "new"));
+
+        let m = router
+            .recognize_with_method(&Method::POST, "/posts/new")
+            .unwrap();
+        assert_eq!(*m.handler, "any");
+    }
+
+    #[test]
+    fn mount() {
+        let mut comments = Router::new();
+        comments.add("/", "comments");
+        comments.add("/:id", "comment");
+        comments.add_with_method(Method::DELETE, "/:id", "destroy");
+        comments.add_with_priority("/:id/:action", 1, "action");
+
+        let mut router = Router::new();
+        router.add("/posts/:post_id", "post");
+        router.add("/posts/:post_id/comments/:id/edit", "edit");
+        router.mount("/posts/:post_id/comments/", comments);
+
+        let m = router.recognize("/posts/12/comments").unwrap();
+        assert_eq!(*m.handler, "comments");
+        assert_eq!(m.params, params("post_id", "12"));
+
+        let m = router.recognize("/posts/12/comments/100").unwrap();
+        assert_eq!(*m.handler, "comment");
+        assert_eq!(m.params, two_params("post_id", "12", "id", "100"));
+
+        let m = router
+            .recognize_with_method(&Method::DELETE, "/posts/12/comments/100")
+            .unwrap();
+        assert_eq!(*m.handler, "destroy");
+
+        assert_eq!(*router.recognize("/posts/12").unwrap().handler, "post");
+
+        let m = router.recognize("/posts/12/comments/100/edit").unwrap();
+        assert_eq!(*m.handler, "action");
+    }
+
+    #[test]
+    fn recognize_prefix() {
+        let mut router = Router::new();
+
+        router.add("/", "root");
+        router.add("/static", "static");
+        router.add("/static/:dir", "dir");
+        router.add("/static/css", "css");
+
+        let m = router.recognize_prefix("/static/css/main.css").unwrap();
+        assert_eq!(*m.handler, "css");
+        assert_eq!(m.prefix(), "/static/css");
+        assert_eq!(m.rest(), "/main.css");
+
+        let m = router.recognize_prefix("/static/js/main.js").unwrap();
+        assert_eq!(*m.handler, "dir");
+        assert_eq!(m.params, params("dir", "js"));
+        assert_eq!(m.rest(), "/main.js");
+
+        let m = router.recognize_prefix("/static").unwrap();
+        assert_eq!(*m.handler, "static");
+        assert_eq!(m.rest(), "");
+
+        let m = router.recognize_prefix("/statics/a").unwrap();
+        assert_eq!(*m.handler, "root");
+        assert_eq!(m.prefix(), "");
+        assert_eq!(m.rest(), "/statics/a");
+
+        let mut router = Router::new();
+        router.add("/static", "static");
+        assert!(router.recognize_prefix("/statics").is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let mut router = Router::new();
+        router.add("/posts/:id", "id".to_string());
+        router.add("/posts/new", "new".to_string());
+        router.add_with_method(Method::DELETE, "/posts/:id", "destroy".to_string());
+        router.add("/files/*path", "файлы".to_string());
+
+        let json = serde_json::to_string(&router).unwrap();
+        let router: Router<String> = serde_json::from_str(&json).unwrap();
+
+        let m = router.recognize("/posts/1").unwrap();
+        assert_eq!(*m.handler, "id");
+        assert_eq!(m.params, params("id", "1"));
+
+        assert_eq!(*router.recognize("/posts/new").unwrap().handler, "new");
+
+        let m = router
+            .recognize_with_method(&Method::DELETE, "/posts/1")
+            .unwrap();
+        assert_eq!(*m.handler, "destroy");
+
+        let m = router.recognize("/files/a/b").unwrap();
+        assert_eq!(m.params, params("path", "a/b"));
+    }
+
+    #[test]
+    fn to_dot() {
+        let mut router = Router::new();
+        router.add("/a/:b", ());
+
+        let dot = router.to_dot();
+        assert!(dot.contains("/a/:b\\nstatics: 1, dynamics: 1, wildcards: 0"));
+        assert!(dot.contains("    2 -> 3 [label=\"[^/]\"];"));
+    }
+
+    #[test]
+    fn routes() {
+        let mut router = Router::new();
+        router.add("/posts/:post_id/comments/:id", "comment".to_string());
+        router.add("posts", "posts".to_string());
+        router.add("/files/*", "files".to_string());
+        router.add_with_method(Method::GET, "/posts", "index".to_string());
+
+        let routes: Vec<_> = router
+            .routes()
+            .map(|r| (r.pattern(), r.method().cloned(), r.handler().as_str()))
+            .collect();
+        assert_eq!(
+            routes,
+            vec![
+                ("/files/*", None, "files"),
+                ("/posts", None, "posts"),
+                ("/posts", Some(Method::GET), "index"),
+                ("/posts/:post_id/comments/:id", None, "comment"),
+            ]
+        );
+
+        let comment = router.routes().last().unwrap();
+        assert_eq!(comment.param_names(), vec!["post_id", "id"]);
+        assert_eq!(
+            (comment.statics(), comment.dynamics(), comment.wildcards()),
+            (2, 2, 0)
+        );
+
+        let files = router.routes().next().unwrap();
+        assert!(files.param_names().is_empty());
+        assert_eq!(files.wildcards(), 1);
+
+        for (pattern, _, handler) in router.iter_mut() {
+            handler.insert_str(0, pattern);
+        }
+        let m = router.recognize("/posts").unwrap();
+        assert_eq!(*m.handler, "/postsposts");
+    }
+
+    #[test]
+    fn tie_break() {
+        let mut first = Router::new();
+        first.add("/x/:b", "b");
+        first.add("/:a/x", "a");
+        first.add("/x/:c", "c");
+
+        let mut last = first.clone();
+        last.set_tie_break(TieBreak::LastAdded);
+
+        let m = first.recognize("/x/x").unwrap();
+        assert_eq!(*m.handler, "c");
+        assert_eq!(m.params, params("c", "x"));
+        assert_eq!(*last.recognize("/x/x").unwrap().handler, "a");
+
+        let handlers: Vec<_> = first
+            .recognize_all("/x/x")
+            .iter()
+            .map(|m| *m.handler)
+            .collect();
+        assert_eq!(handlers, vec!["c", "a"]);
+        let handlers: Vec<_> = last
+            .recognize_all("/x/x")
+            .iter()
+            .map(|m| *m.handler)
+            .collect();
+        assert_eq!(handlers, vec!["a", "c"]);
+    }
+
+    #[test]
+    fn positional_ranking() {
+        let mut router = Router::new();
+        router.set_ranking(Ranking::Positional);
+
+        router.add("/:a/b/c", "abc");
+        router.add("/a/*rest", "rest");
+        router.add("/a/:b", "ab");
+        router.add("/a/:b/:c", "abc2");
+        router.add("/:a/:b.json", "json");
+
+        let handlers: Vec<_> = router
+            .recognize_all("/a/b/c")
+            .iter()
+            .map(|m| *m.handler)
+            .collect();
+        assert_eq!(handlers, vec!["abc2", "rest", "abc"]);
+
+        let m = router.recognize("/a/b.json").unwrap();
+        assert_eq!(*m.handler, "ab");
+
+        let m = router.recognize("/x/b.json").unwrap();
+        assert_eq!(*m.handler, "json");
+        assert_eq!(m.params, two_params("a", "x", "b", "b"));
+
+        router.set_ranking(Ranking::Counts);
+        let handlers: Vec<_> = router
+            .recognize_all("/a/b/c")
+            .iter()
+            .map(|m| *m.handler)
+            .collect();
+        assert_eq!(handlers, vec!["abc", "abc2", "rest"]);
+    }
+
+    #[test]
+    fn priority() {
+        let mut router = Router::new();
+        router.add_with_priority("/*path", -1, "cms");
+        router.add("/posts/:id", "post");
+        router.add("/:section", "section");
+
+        assert_eq!(*router.recognize("/about").unwrap().handler, "section");
+        assert_eq!(*router.recognize("/a/b").unwrap().handler, "cms");
+
+        router.add_with_priority("/posts/*rest", 1, "posts");
+        let handlers: Vec<_> = router
+            .recognize_all("/posts/1")
+            .iter()
+            .map(|m| *m.handler)
+            .collect();
+        assert_eq!(handlers, vec!["posts", "post", "cms"]);
+
+        // Replacing the handler of a route keeps the route's priority.
+        router.add("/*path", "fallback");
+        assert_eq!(*router.recognize("/a/b").unwrap().handler, "fallback");
+        let route = router.routes().find(|r| r.pattern() == "/*path").unwrap();
+        assert_eq!(route.priority(), -1);
+        assert_eq!(*router.recognize("/about").unwrap().handler, "section");
+
+        router.add_with_priority("/*path", 0, "fallback");
+        let route = router.routes().find(|r| r.pattern() == "/*path").unwrap();
+        assert_eq!(route.priority(), 0);
+    }
+
+    #[test]
+    fn ranker() {
+        let mut router = Router::new();
+        router.add("/posts/:id", "post");
+        router.add("/posts/new", "new");
+        router.add("/posts/*rest", "rest");
+        router.add("/:section/:id", "section");
+        router.add_with_priority("/*path", -1, "fallback");
+
+        // Routes ending in a numeric param win, then the user's priority, then
+        // static segments.
+        router.set_ranker(|first: &RouteInfo<'_>, second: &RouteInfo<'_>| {
+            let numeric = |route: &RouteInfo<'_>| {
+                route.kinds().last() == Some(&SegmentKind::Dynamic)
+                    && route
+                        .captures()
+                        .last()
+                        .is_some_and(|c| c.bytes().all(|b| b.is_ascii_digit()))
+            };
+            let statics = |route: &RouteInfo<'_>| {
+                route
+                    .kinds()
+                    .iter()
+                    .filter(|&&kind| kind == SegmentKind::Static)
+                    .count()
+            };
+            numeric(first)
+                .cmp(&numeric(second))
+                .then(first.priority().cmp(&second.priority()))
+                .then(statics(first).cmp(&statics(second)))
+        });
+
+        assert_eq!(*router.recognize("/posts/new").unwrap().handler, "new");
+        let handlers: Vec<_> = router
+            .recognize_all("/posts/1")
+            .iter()
+            .map(|m| *m.handler)
+            .collect();
+        assert_eq!(handlers, vec!["post", "section", "rest", "fallback"]);
+        let explanation = router.explain("/posts/1");
+        assert_eq!(explanation.candidates()[1].pattern(), "/:section/:id");
+
+        router.set_ranking(Ranking::Counts);
+        let handlers: Vec<_> = router
+            .recognize_all("/posts/1")
+            .iter()
+            .map(|m| *m.handler)
+            .collect();
+        assert_eq!(handlers, vec!["post", "rest", "section", "fallback"]);
     }
 
     fn params(key: &str, val: &str) -> Params {
@@ -0,0 +1,539 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+
+use crate::nfa::{Explorer, Side};
+use crate::{Endpoint, Metadata, Method, Router};
+
+/// A route that can never be recognized, found by `Router::lint`.
+///
+/// Every path the route matches is also matched by a route with a higher
+/// priority that handles the same method.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Shadowed {
+    pattern: String,
+    method: Option<Method>,
+    examples: Vec<Example>,
+}
+
+impl Shadowed {
+    /// Get the pattern of the shadowed route.
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Get the method of the shadowed route, or `None` if it handles any
+    /// method.
+    pub fn method(&self) -> Option<&Method> {
+        self.method.as_ref()
+    }
+
+    /// Get example paths matched by the route, one for every route that wins
+    /// over it.
+    pub fn examples(&self) -> &[Example] {
+        &self.examples
+    }
+}
+
+impl fmt::Display for Shadowed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(ref method) = self.method {
+            write!(f, "{} ", method)?;
+        }
+        write!(f, "{} is shadowed", self.pattern)?;
+        for (i, example) in self.examples.iter().enumerate() {
+            f.write_str(if i == 0 { ": " } else { ", " })?;
+            write!(f, "{} is matched by {}", example.path, example.winner)?;
+        }
+        Ok(())
+    }
+}
+
+/// A path along with the route that wins for it.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Example {
+    path: String,
+    winner: String,
+}
+
+impl Example {
+    /// Get the example path.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Get the pattern of the route that wins for the path.
+    pub fn winner(&self) -> &str {
+        &self.winner
+    }
+}
+
+/// Two routes that match the same path with equal priority, found by
+/// `Router::ambiguities` and `Router::try_add`.
+///
+/// Which of the two routes wins for such a path only depends on the
+/// `TieBreak` of the router.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Ambiguity {
+    first: String,
+    second: String,
+    example: String,
+}
+
+impl Ambiguity {
+    /// Get the pattern of the first route.
+    pub fn first(&self) -> &str {
+        &self.first
+    }
+
+    /// Get the pattern of the second route.
+    pub fn second(&self) -> &str {
+        &self.second
+    }
+
+    /// Get a path that both routes match.
+    pub fn example(&self) -> &str {
+        &self.example
+    }
+}
+
+impl fmt::Display for Ambiguity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} and {} both match {} with equal priority",
+            self.first, self.second, self.example
+        )
+    }
+}
+
+impl Error for Ambiguity {}
+
+impl<T> Router<T> {
+    /// Find the pairs of routes that match the same path with equal priority.
+    ///
+    /// Only routes that handle a common method are reported.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use route_recognizer::Router;
+    ///
+    /// let mut router = Router::new();
+    /// router.add("/:a/x", "a");
+    /// router.add("/x/:b", "b");
+    ///
+    /// let ambiguities = router.ambiguities();
+    /// assert_eq!(ambiguities.len(), 1);
+    /// assert_eq!(ambiguities[0].example(), "/x/x");
+    /// ```
+    pub fn ambiguities(&self) -> Vec<Ambiguity> {
+        let explorer = Explorer::new(&self.nfa);
+        let sides: BTreeMap<usize, Side> = self
+            .handlers
+            .keys()
+            .map(|&state| (state, explorer.side(&[state])))
+            .collect();
+        let states: Vec<usize> = sides.keys().cloned().collect();
+        let mut ambiguities = vec![];
+
+        for (i, &first) in states.iter().enumerate() {
+            for &second in &states[i + 1..] {
+                if !overlaps(&self.handlers[&first], &self.handlers[&second]) {
+                    continue;
+                }
+                let ambiguity = self.ambiguity(
+                    &explorer,
+                    (first, &sides[&first]),
+                    (second, &sides[&second]),
+                );
+                if let Some(ambiguity) = ambiguity {
+                    ambiguities.push(ambiguity);
+                }
+            }
+        }
+
+        ambiguities.sort_by(|a, b| (&a.first, &a.second).cmp(&(&b.first, &b.second)));
+        ambiguities
+    }
+
+    /// Add a route to the router, unless it is ambiguous with a route that
+    /// was added before.
+    ///
+    /// This is a strict version of `add`. If some path would be matched with
+    /// equal priority by the new route and an existing one, or the new route
+    /// matches the same paths as an existing one, the router is left
+    /// unchanged and the conflict is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use route_recognizer::Router;
+    ///
+    /// let mut router = Router::new();
+    /// router.try_add("/:a/x", "a").unwrap();
+    ///
+    /// let err = router.try_add("/x/:b", "b").unwrap_err();
+    /// assert_eq!(err.to_string(), "/x/:b and /:a/x both match /x/x with equal priority");
+    /// assert!(router.recognize("/x/y").is_err());
+    /// ```
+    pub fn try_add(&mut self, route: &str, dest: T) -> Result<(), Ambiguity> {
+        let sequence = self.sequence;
+        self.nfa.checkpoint();
+        let state = self.insert(route);
+
+        let explorer = Explorer::new(&self.nfa);
+        let side = explorer.side(&[state]);
+        let mut ambiguity = None;
+        if self.handlers.contains_key(&state) {
+            // The new route ends in the state of an existing one, so adding it
+            // would replace that route.
+            ambiguity = explorer.common(&side, &side).map(|path| (state, path));
+        } else {
+            // Explore the new route against every route it could tie with at
+            // once, and report the first route it shares a path with.
+            let rivals: Vec<usize> = self
+                .handlers
+                .keys()
+                .cloned()
+                .filter(|&other| self.rank(state).priority(&self.rank(other)) == Ordering::Equal)
+                .collect();
+            explorer.explore(
+                &side,
+                &explorer.side(&rivals),
+                |path, accepted| match accepted.first() {
+                    Some(&other) => {
+                        ambiguity = Some((other, path.to_string()));
+                        false
+                    }
+                    None => true,
+                },
+            );
+        }
+
+        match ambiguity {
+            Some((other, path)) => {
+                let first = self.metadata(state).route.clone();
+                // Rolling back restores the route that `other` was added with,
+                // even if the new route ends in the same state.
+                self.nfa.rollback();
+                self.sequence = sequence;
+                Err(Ambiguity {
+                    first,
+                    second: self.metadata(other).route.clone(),
+                    example: format!("/{}", path),
+                })
+            }
+            None => {
+                self.nfa.commit();
+                self.endpoint(state).any = Some(dest);
+                Ok(())
+            }
+        }
+    }
+
+    fn ambiguity(
+        &self,
+        explorer: &Explorer<'_, Metadata>,
+        (first, first_side): (usize, &Side),
+        (second, second_side): (usize, &Side),
+    ) -> Option<Ambiguity> {
+        if self.rank(first).priority(&self.rank(second)) != Ordering::Equal {
+            return None;
+        }
+        if !explorer.may_overlap(first_side, second_side) {
+            return None;
+        }
+
+        let example = explorer.common(first_side, second_side)?;
+
+        Some(Ambiguity {
+            first: self.metadata(first).route.clone(),
+            second: self.metadata(second).route.clone(),
+            example: format!("/{}", example),
+        })
+    }
+
+    /// Find the routes that can never be recognized.
+    ///
+    /// A route is shadowed when every path it matches is also matched by a
+    /// route that ranks higher and handles the same method. Routes with
+    /// validators never shadow other routes, since they may reject a path.
+    /// This is checked on the routing automaton, so it holds for any path,
+    /// not just the examples.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use route_recognizer::Router;
+    ///
+    /// let mut router = Router::new();
+    /// router.add("/files/:name", "file");
+    /// router.add("/files/:dir/*rest", "nested");
+    /// router.add("/files/*path", "fallback");
+    /// assert!(router.lint().is_empty());
+    ///
+    /// // Every path that is left for the fallback has an empty segment.
+    /// router.add("/files/*dir/", "directory");
+    /// router.add("/files//*rest", "absolute");
+    /// router.add("/files//", "root");
+    ///
+    /// for shadowed in router.lint() {
+    ///     println!("{}", shadowed);
+    /// }
+    /// assert_eq!(router.lint()[0].pattern(), "/files/*path");
+    /// ```
+    pub fn lint(&self) -> Vec<Shadowed> {
+        let explorer = Explorer::new(&self.nfa);
+        let mut shadowed = vec![];
+
+        for (&state, endpoint) in &self.handlers {
+            let methods = endpoint
+                .any
+                .iter()
+                .map(|_| None)
+                .chain(endpoint.methods.keys().map(Some));
+
+            for method in methods {
+                let handles = |other: usize| match method {
+                    None => self.handlers[&other].any.is_some(),
+                    Some(method) => self.handlers[&other].get(method).is_some(),
+                };
+                let rivals: Vec<usize> = self
+                    .handlers
+                    .keys()
+                    .cloned()
+                    .filter(|&other| self.rank(other) > self.rank(state) && handles(other))
+                    .filter(|&other| self.metadata(other).validators.iter().all(Option::is_none))
+                    .collect();
+
+                if rivals.is_empty() {
+                    continue;
+                }
+
+                if let Some(examples) = self.shadowing(&explorer, state, &rivals) {
+                    shadowed.push(Shadowed {
+                        pattern: self.metadata(state).route.clone(),
+                        method: method.cloned(),
+                        examples,
+                    });
+                }
+            }
+        }
+
+        shadowed.sort_by(|a, b| (&a.pattern, &a.method).cmp(&(&b.pattern, &b.method)));
+        shadowed
+    }
+
+    // Checks whether every path matched by `state` is matched by one of
+    // `rivals`, returning an example for every rival that wins for some path.
+    fn shadowing(
+        &self,
+        explorer: &Explorer<'_, Metadata>,
+        state: usize,
+        rivals: &[usize],
+    ) -> Option<Vec<Example>> {
+        let mut examples: Vec<Example> = vec![];
+        let mut covered = true;
+
+        let side = explorer.side(&[state]);
+        explorer.explore(&side, &explorer.side(rivals), |path, accepted| {
+            let winner = match accepted.iter().max_by_key(|&&other| self.rank(other)) {
+                Some(&winner) => winner,
+                None => {
+                    covered = false;
+                    return false;
+                }
+            };
+
+            let winner = &self.metadata(winner).route;
+            if !examples.iter().any(|example| example.winner == *winner) {
+                examples.push(Example {
+                    path: format!("/{}", path),
+                    winner: winner.clone(),
+                });
+            }
+            true
+        });
+
+        if covered && !examples.is_empty() {
+            Some(examples)
+        } else {
+            None
+        }
+    }
+}
+
+// Whether some request method is handled by both endpoints.
+fn overlaps<T>(first: &Endpoint<T>, second: &Endpoint<T>) -> bool {
+    first.any.is_some()
+        || second.any.is_some()
+        || first
+            .methods
+            .keys()
+            .any(|method| second.methods.contains_key(method))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Method, Router};
+
+    fn files() -> Router<()> {
+        let mut router = Router::new();
+        router.add("/files/:name", ());
+        router.add("/files/:dir/*rest", ());
+        router.add("/files/*dir/", ());
+        router.add("/files//*rest", ());
+        router.add("/files//", ());
+        router
+    }
+
+    #[test]
+    fn lint_clean() {
+        let mut router = Router::new();
+        router.add("/posts", ());
+        router.add("/posts/new", ());
+        router.add("/posts/:id", ());
+        router.add("/posts/:id/edit", ());
+        router.add("/:section/:id", ());
+        router.add("/*path", ());
+
+        assert!(router.lint().is_empty());
+    }
+
+    #[test]
+    fn lint_shadowed() {
+        let mut router = files();
+        router.add("/files/*path", ());
+
+        let shadowed = router.lint();
+        assert_eq!(shadowed.len(), 1);
+        assert_eq!(shadowed[0].pattern(), "/files/*path");
+        assert_eq!(shadowed[0].method(), None);
+
+        let examples: Vec<_> = shadowed[0]
+            .examples()
+            .iter()
+            .map(|example| (example.path(), example.winner()))
+            .collect();
+        assert_eq!(
+            examples,
+            vec![
+                ("/files/a", "/files/:name"),
+                ("/files//", "/files//"),
+                ("/files/a/", "/files/*dir/"),
+                ("/files//a", "/files//*rest"),
+                ("/files/a/a", "/files/:dir/*rest"),
+            ]
+        );
+        assert!(shadowed[0].to_string().starts_with(
+            "/files/*path is shadowed: /files/a is matched by /files/:name, \
+             /files// is matched by /files//, "
+        ));
+    }
+
+    #[test]
+    fn lint_methods() {
+        let mut router = Router::new();
+        router.add_with_method(Method::GET, "/files/:name", ());
+        router.add_with_method(Method::GET, "/files/:dir/*rest", ());
+        router.add_with_method(Method::GET, "/files/*dir/", ());
+        router.add_with_method(Method::GET, "/files//*rest", ());
+        router.add_with_method(Method::GET, "/files//", ());
+        router.add_with_method(Method::GET, "/files/*path", ());
+        router.add_with_method(Method::PUT, "/files/*path", ());
+
+        let shadowed = router.lint();
+        assert_eq!(shadowed.len(), 1);
+        assert_eq!(shadowed[0].method(), Some(&Method::GET));
+        assert!(shadowed[0]
+            .to_string()
+            .starts_with("GET /files/*path is shadowed: "));
+    }
+
+    #[test]
+    fn ambiguities() {
+        let mut router = Router::new();
+        router.add("/:a/x", ());
+        router.add("/x/:b", ());
+        router.add("/x/x", ());
+        router.add("/:a/:b", ());
+        router.add("/:a.json", ());
+        router.add("/x.:ext", ());
+
+        let ambiguities: Vec<_> = router
+            .ambiguities()
+            .iter()
+            .map(|a| {
+                (
+                    a.first().to_string(),
+                    a.second().to_string(),
+                    a.example().to_string(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            ambiguities,
+            vec![
+                (
+                    "/:a.json".to_string(),
+                    "/x.:ext".to_string(),
+                    "/x.json".to_string()
+                ),
+                ("/:a/x".to_string(), "/x/:b".to_string(), "/x/x".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn ambiguities_methods() {
+        let mut router = Router::new();
+        router.add_with_method(Method::GET, "/:a/x", ());
+        router.add_with_method(Method::POST, "/x/:b", ());
+        assert!(router.ambiguities().is_empty());
+
+        router.add("/x/:b", ());
+        assert_eq!(router.ambiguities().len(), 1);
+    }
+
+    #[test]
+    fn try_add() {
+        let mut router = Router::new();
+        router.try_add("/posts/:id", "id").unwrap();
+        router.try_add("/posts/new", "new").unwrap();
+        router.try_add("/:section/new", "section").unwrap_err();
+        assert!(router.recognize("/comments/new").is_err());
+        assert_eq!(router.routes().count(), 2);
+
+        // A route that ends in the state of an existing one leaves that route
+        // as it was.
+        let err = router.try_add("/posts/:post_id", "post").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "/posts/:post_id and /posts/:id both match /posts/a with equal priority"
+        );
+        let m = router.recognize("/posts/1").unwrap();
+        assert_eq!(*m.handler(), &"id");
+        assert_eq!(m.params().find("id"), Some("1"));
+        assert_eq!(m.params().find("post_id"), None);
+
+        let mut router = Router::new();
+        router.add("/a/*x?", "opt");
+        router.try_add("/a/*y", "req").unwrap_err();
+        let m = router.recognize("/a").unwrap();
+        assert_eq!(*m.handler(), &"opt");
+        assert_eq!(m.params().find("x"), Some(""));
+    }
+
+    #[test]
+    fn many_routes() {
+        let mut router = Router::new();
+        for i in 0..300 {
+            router.try_add(&format!("/r{}/:a/:b", i), ()).unwrap();
+        }
+        assert!(router.ambiguities().is_empty());
+        let err = router.try_add("/:a/r1/:b", ()).unwrap_err();
+        assert_eq!(err.example(), "/r0/r1/a");
+    }
+}
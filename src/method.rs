@@ -0,0 +1,68 @@
+use std::borrow::Cow;
+use std::fmt;
+
+/// An HTTP request method.
+///
+/// The standard methods are available as associated constants. Extension
+/// methods can be created with `Method::from`. Method names are case
+/// sensitive.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Method(Cow<'static, str>);
+
+impl Method {
+    /// The `GET` method.
+    pub const GET: Method = Method(Cow::Borrowed("GET"));
+    /// The `HEAD` method.
+    pub const HEAD: Method = Method(Cow::Borrowed("HEAD"));
+    /// The `POST` method.
+    pub const POST: Method = Method(Cow::Borrowed("POST"));
+    /// The `PUT` method.
+    pub const PUT: Method = Method(Cow::Borrowed("PUT"));
+    /// The `DELETE` method.
+    pub const DELETE: Method = Method(Cow::Borrowed("DELETE"));
+    /// The `CONNECT` method.
+    pub const CONNECT: Method = Method(Cow::Borrowed("CONNECT"));
+    /// The `OPTIONS` method.
+    pub const OPTIONS: Method = Method(Cow::Borrowed("OPTIONS"));
+    /// The `TRACE` method.
+    pub const TRACE: Method = Method(Cow::Borrowed("TRACE"));
+    /// The `PATCH` method.
+    pub const PATCH: Method = Method(Cow::Borrowed("PATCH"));
+
+    /// Get the method name.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for Method {
+    fn from(name: &str) -> Self {
+        Method(Cow::Owned(name.to_string()))
+    }
+}
+
+impl From<String> for Method {
+    fn from(name: String) -> Self {
+        Method(Cow::Owned(name))
+    }
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Method;
+
+    #[test]
+    fn from_str() {
+        assert_eq!(Method::from("GET"), Method::GET);
+        assert_ne!(Method::from("get"), Method::GET);
+        assert_eq!(Method::from("PURGE").as_str(), "PURGE");
+        assert_eq!(Method::DELETE.to_string(), "DELETE");
+    }
+}
@@ -1,8 +1,11 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::{self, Write};
+use std::mem;
 
 use self::CharacterClass::{Ascii, InvalidChars, ValidChars};
 
 #[derive(PartialEq, Eq, Clone, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CharSet {
     low_mask: u64,
     high_mask: u64,
@@ -32,6 +35,16 @@ impl CharSet {
         }
     }
 
+    pub fn chars(&self) -> Vec<char> {
+        let mut chars: Vec<char> = (1..=128)
+            .filter_map(std::char::from_u32)
+            .filter(|&char| self.contains(char))
+            .chain(self.non_ascii.iter().cloned())
+            .collect();
+        chars.sort_unstable();
+        chars
+    }
+
     pub fn contains(&self, char: char) -> bool {
         let val = char as u32 - 1;
 
@@ -48,6 +61,7 @@ impl CharSet {
 }
 
 #[derive(PartialEq, Eq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CharacterClass {
     Ascii(u64, u64, bool),
     ValidChars(CharSet),
@@ -56,7 +70,7 @@ pub enum CharacterClass {
 
 impl CharacterClass {
     pub fn any() -> Self {
-        Ascii(u64::max_value(), u64::max_value(), true)
+        Ascii(u64::MAX, u64::MAX, true)
     }
 
     pub fn valid(string: &str) -> Self {
@@ -85,9 +99,32 @@ impl CharacterClass {
         if val > 127 {
             InvalidChars(Self::char_to_set(char))
         } else if val > 63 {
-            Ascii(u64::max_value() ^ (1 << (val - 64)), u64::max_value(), true)
+            Ascii(u64::MAX ^ (1 << (val - 64)), u64::MAX, true)
         } else {
-            Ascii(u64::max_value(), u64::max_value() ^ (1 << val), true)
+            Ascii(u64::MAX, u64::MAX ^ (1 << val), true)
+        }
+    }
+
+    // The only character the class matches, if it matches exactly one.
+    pub fn single(&self) -> Option<char> {
+        match *self {
+            ValidChars(ref valid) if valid.non_ascii.len() == 1 => {
+                let ascii = valid.low_mask == 0 && valid.high_mask == 0;
+                valid.non_ascii.iter().cloned().find(|_| ascii)
+            }
+            ValidChars(ref valid) if valid.non_ascii.is_empty() => {
+                let chars = valid.chars();
+                chars.first().cloned().filter(|_| chars.len() == 1)
+            }
+            Ascii(high, low, false) if high.count_ones() + low.count_ones() == 1 => {
+                let val = if low != 0 {
+                    low.trailing_zeros()
+                } else {
+                    high.trailing_zeros() + 64
+                };
+                std::char::from_u32(val + 1)
+            }
+            _ => None,
         }
     }
 
@@ -123,6 +160,34 @@ impl CharacterClass {
     }
 }
 
+impl fmt::Display for CharacterClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (negated, chars) = match *self {
+            ValidChars(ref set) => (false, set.chars()),
+            InvalidChars(ref set) => (true, set.chars()),
+            Ascii(_, _, unicode) => {
+                let chars = (1..=128)
+                    .filter_map(std::char::from_u32)
+                    .filter(|&char| self.matches(char) != unicode)
+                    .collect();
+                (unicode, chars)
+            }
+        };
+
+        match (negated, &chars[..]) {
+            (true, []) => f.write_str("any"),
+            (false, [char]) => write!(f, "{}", char.escape_debug()),
+            _ => {
+                f.write_str(if negated { "[^" } else { "[" })?;
+                for char in &chars {
+                    write!(f, "{}", char.escape_debug())?;
+                }
+                f.write_str("]")
+            }
+        }
+    }
+}
+
 #[derive(Clone)]
 struct Thread {
     state: usize,
@@ -159,6 +224,7 @@ impl Thread {
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct State<T> {
     pub index: usize,
     pub chars: CharacterClass,
@@ -167,6 +233,15 @@ pub struct State<T> {
     pub start_capture: bool,
     pub end_capture: bool,
     pub metadata: Option<T>,
+    // Set on states that only belong to routes with the same constraint, so
+    // that `put` does not share them with unconstrained routes.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub tag: Option<String>,
+    // Set on acceptance states that stand in for another acceptance state,
+    // for routes that can end in more than one state. Matches report the
+    // state they stand in for.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub alias: Option<usize>,
 }
 
 impl<T> PartialEq for State<T> {
@@ -185,6 +260,8 @@ impl<T> State<T> {
             start_capture: false,
             end_capture: false,
             metadata: None,
+            tag: None,
+            alias: None,
         }
     }
 }
@@ -201,12 +278,40 @@ impl<'a> Match<'a> {
     }
 }
 
+#[derive(Debug)]
+pub struct Trace {
+    pub consumed: usize,
+    pub accepted: bool,
+    pub expected: Vec<usize>,
+}
+
 #[derive(Clone, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NFA<T> {
     states: Vec<State<T>>,
     start_capture: Vec<bool>,
     end_capture: Vec<bool>,
     acceptance: Vec<bool>,
+    #[cfg_attr(feature = "serde", serde(skip, default = "Option::default"))]
+    journal: Option<Journal<T>>,
+}
+
+// The changes made to the states that existed at a checkpoint, most recent
+// last. States added since the checkpoint are simply dropped on rollback.
+#[derive(Clone, Debug)]
+struct Journal<T> {
+    len: usize,
+    undo: Vec<Undo<T>>,
+}
+
+#[derive(Clone, Debug)]
+enum Undo<T> {
+    NextState(usize),
+    Acceptance(usize, bool),
+    StartCapture(usize),
+    EndCapture(usize),
+    Metadata(usize, Option<T>),
+    Alias(usize, Option<usize>),
 }
 
 impl<T> NFA<T> {
@@ -217,6 +322,7 @@ impl<T> NFA<T> {
             start_capture: vec![false],
             end_capture: vec![false],
             acceptance: vec![false],
+            journal: None,
         }
     }
 
@@ -225,17 +331,7 @@ impl<T> NFA<T> {
         I: Ord,
         F: FnMut(usize) -> I,
     {
-        let mut threads = vec![Thread::new()];
-
-        for (i, char) in string.char_indices() {
-            let next_threads = self.process_char(threads, char, i);
-
-            if next_threads.is_empty() {
-                return Err(format!("Couldn't process {}", string));
-            }
-
-            threads = next_threads;
-        }
+        let threads = self.run(string)?;
 
         let returned = threads
             .into_iter()
@@ -243,7 +339,7 @@ impl<T> NFA<T> {
 
         let thread = returned
             .fold(None, |prev, y| {
-                let y_v = ord(y.state);
+                let y_v = ord(self.canonical(y.state));
                 match prev {
                     None => Some((y_v, y)),
                     Some((x_v, x)) => {
@@ -261,14 +357,193 @@ impl<T> NFA<T> {
             None => Err("The string was exhausted before reaching an \
                          acceptance state"
                 .to_string()),
-            Some(mut thread) => {
-                if thread.capture_begin.is_some() {
-                    thread.end_capture(string.len());
+            Some(thread) => Ok(self.finish(thread, string)),
+        }
+    }
+
+    // Like `process`, but returns one match per acceptance state reached,
+    // ordered from the highest to the lowest `ord`.
+    pub fn process_all<'a, I, F>(&self, string: &'a str, mut ord: F) -> Vec<Match<'a>>
+    where
+        I: Ord,
+        F: FnMut(usize) -> I,
+    {
+        let threads = match self.run(string) {
+            Ok(threads) => threads,
+            Err(_) => return Vec::new(),
+        };
+
+        let mut seen = HashSet::new();
+        let mut returned: Vec<(I, Thread)> = threads
+            .into_iter()
+            .filter(|thread| self.get(thread.state).acceptance)
+            .filter(|thread| seen.insert(self.canonical(thread.state)))
+            .map(|thread| (ord(self.canonical(thread.state)), thread))
+            .collect();
+
+        returned.sort_by(|(x_v, _), (y_v, _)| y_v.cmp(x_v));
+
+        returned
+            .into_iter()
+            .map(|(_, thread)| self.finish(thread, string))
+            .collect()
+    }
+
+    // Finds the longest prefix of `string` that is empty, ends right before a
+    // `boundary` character, or spans all of `string`, and is accepted by the
+    // NFA. Matches for which `ord` returns `None` are not considered
+    // accepting. Returns the best match for that prefix along with the prefix
+    // length.
+    pub fn process_prefix<'a, I, F>(
+        &self,
+        string: &'a str,
+        boundary: char,
+        mut ord: F,
+    ) -> Option<(Match<'a>, usize)>
+    where
+        I: Ord,
+        F: FnMut(&Match<'a>) -> Option<I>,
+    {
+        let mut threads = vec![Thread::new()];
+        let mut best = None;
+
+        let ends = string
+            .char_indices()
+            .map(|(i, char)| (i, Some(char)))
+            .chain(Some((string.len(), None)));
+
+        for (i, char) in ends {
+            if i == 0 || char.map_or(true, |char| char == boundary) {
+                let mut found: Option<(I, Match<'a>)> = None;
+                for thread in &threads {
+                    if !self.get(thread.state).acceptance {
+                        continue;
+                    }
+                    let m = self.finish(thread.clone(), &string[..i]);
+                    if let Some(v) = ord(&m) {
+                        if found.as_ref().map_or(true, |(x_v, _)| *x_v < v) {
+                            found = Some((v, m));
+                        }
+                    }
+                }
+                if let Some((_, m)) = found {
+                    best = Some((m, i));
                 }
-                let state = self.get(thread.state);
-                Ok(Match::new(state.index, thread.extract(string)))
+            }
+
+            let char = match char {
+                Some(char) => char,
+                None => break,
+            };
+
+            threads = self.process_char(threads, char, i);
+            if threads.is_empty() {
+                break;
             }
         }
+
+        best
+    }
+
+    // Follows `string` through the states that can still reach `target`.
+    // Reports how many characters were consumed before no such state was
+    // left, whether `target` was reached at the end, and which states would
+    // have allowed the match to continue.
+    pub fn trace(&self, string: &str, target: usize) -> Trace {
+        let targets = self.accepting_as(target);
+        let reaching = self.reaching(&targets);
+        let mut current = vec![0];
+        let mut consumed = 0;
+
+        let expected = |current: &[usize]| {
+            let mut expected: Vec<usize> = current
+                .iter()
+                .flat_map(|&index| self.get(index).next_states.iter().cloned())
+                .filter(|&index| reaching[index])
+                .collect();
+            expected.sort_unstable();
+            expected.dedup();
+            expected
+        };
+
+        for char in string.chars() {
+            let mut next: Vec<usize> = expected(&current)
+                .into_iter()
+                .filter(|&index| self.get(index).chars.matches(char))
+                .collect();
+            next.dedup();
+
+            if next.is_empty() {
+                return Trace {
+                    consumed,
+                    accepted: false,
+                    expected: expected(&current),
+                };
+            }
+
+            current = next;
+            consumed += 1;
+        }
+
+        Trace {
+            consumed,
+            accepted: current.iter().any(|index| targets.contains(index)),
+            expected: expected(&current),
+        }
+    }
+
+    // Explores the strings that reach `left` alongside the states of `right`
+    // they reach. See `Explorer::explore`.
+    pub fn explore<F>(&self, left: usize, right: &[usize], visit: F)
+    where
+        F: FnMut(&str, &[usize]) -> bool,
+    {
+        let explorer = Explorer::new(self);
+        explorer.explore(&explorer.side(&[left]), &explorer.side(right), visit);
+    }
+
+    // Marks the states from which one of `targets` can be reached.
+    fn reaching(&self, targets: &[usize]) -> Vec<bool> {
+        let mut reaching = vec![false; self.states.len()];
+        for &target in targets {
+            reaching[target] = true;
+        }
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for state in &self.states {
+                if !reaching[state.index] && state.next_states.iter().any(|&n| reaching[n]) {
+                    reaching[state.index] = true;
+                    changed = true;
+                }
+            }
+        }
+
+        reaching
+    }
+
+    fn run(&self, string: &str) -> Result<Vec<Thread>, String> {
+        let mut threads = vec![Thread::new()];
+
+        for (i, char) in string.char_indices() {
+            let next_threads = self.process_char(threads, char, i);
+
+            if next_threads.is_empty() {
+                return Err(format!("Couldn't process {}", string));
+            }
+
+            threads = next_threads;
+        }
+
+        Ok(threads)
+    }
+
+    fn finish<'a>(&self, mut thread: Thread, string: &'a str) -> Match<'a> {
+        if thread.capture_begin.is_some() {
+            thread.end_capture(string.len());
+        }
+        Match::new(self.canonical(thread.state), thread.extract(string))
     }
 
     #[inline]
@@ -320,45 +595,270 @@ impl<T> NFA<T> {
     }
 
     pub fn put(&mut self, index: usize, chars: CharacterClass) -> usize {
+        self.put_tagged(index, chars, None)
+    }
+
+    // Like `put`, but only reuses a next state with the same `tag`.
+    pub fn put_tagged(&mut self, index: usize, chars: CharacterClass, tag: Option<&str>) -> usize {
         {
             let state = self.get(index);
 
             for &index in &state.next_states {
                 let state = self.get(index);
-                if state.chars == chars {
+                if state.chars == chars && state.tag.as_deref() == tag {
                     return index;
                 }
             }
         }
 
         let state = self.new_state(chars);
-        self.get_mut(index).next_states.push(state);
+        self.get_mut(state).tag = tag.map(str::to_string);
+        self.push_next(index, state);
         state
     }
 
+    // Adds a chain of states matching `classes` after `index`, or finds the
+    // chain that was added with the same `tag` before. Later states in the
+    // chain get lower indices, so moving along the chain does not end a
+    // capture that its states end.
+    pub fn put_chain(&mut self, index: usize, classes: &[CharacterClass], tag: &str) -> Vec<usize> {
+        let find = |nfa: &Self, index: usize, chars: &CharacterClass| {
+            nfa.get(index).next_states.iter().cloned().find(|&next| {
+                let state = nfa.get(next);
+                state.chars == *chars && state.tag.as_deref() == Some(tag)
+            })
+        };
+
+        let mut chain = vec![];
+        let mut last = index;
+        for chars in classes {
+            match find(self, last, chars) {
+                Some(next) => chain.push(next),
+                None => break,
+            }
+            last = chain[chain.len() - 1];
+        }
+        if chain.len() == classes.len() {
+            return chain;
+        }
+
+        let mut chain: Vec<usize> = classes
+            .iter()
+            .rev()
+            .map(|chars| self.new_state(chars.clone()))
+            .collect();
+        chain.reverse();
+        for (i, &state) in chain.iter().enumerate() {
+            self.get_mut(state).tag = Some(tag.to_string());
+            if let Some(&next) = chain.get(i + 1) {
+                self.get_mut(state).next_states.push(next);
+            }
+        }
+        self.push_next(index, chain[0]);
+        chain
+    }
+
     pub fn put_state(&mut self, index: usize, child: usize) {
         if !self.states[index].next_states.contains(&child) {
-            self.get_mut(index).next_states.push(child);
+            self.push_next(index, child);
         }
     }
 
     pub fn acceptance(&mut self, index: usize) {
-        self.get_mut(index).acceptance = true;
-        self.acceptance[index] = true;
+        self.set_acceptance(index, true);
+    }
+
+    fn set_acceptance(&mut self, index: usize, acceptance: bool) {
+        let previous = self.acceptance[index];
+        if previous != acceptance {
+            self.record(index, || Undo::Acceptance(index, previous));
+        }
+        self.get_mut(index).acceptance = acceptance;
+        self.acceptance[index] = acceptance;
     }
 
     pub fn start_capture(&mut self, index: usize) {
+        if !self.start_capture[index] {
+            self.record(index, || Undo::StartCapture(index));
+        }
         self.get_mut(index).start_capture = true;
         self.start_capture[index] = true;
     }
 
     pub fn end_capture(&mut self, index: usize) {
+        if !self.end_capture[index] {
+            self.record(index, || Undo::EndCapture(index));
+        }
         self.get_mut(index).end_capture = true;
         self.end_capture[index] = true;
     }
 
     pub fn metadata(&mut self, index: usize, metadata: T) {
-        self.get_mut(index).metadata = Some(metadata);
+        let previous = self.get_mut(index).metadata.replace(metadata);
+        self.record(index, || Undo::Metadata(index, previous));
+    }
+
+    // Makes `index` an acceptance state that matches report as `target`.
+    pub fn alias(&mut self, index: usize, target: usize) {
+        self.acceptance(index);
+        self.set_alias(index, Some(target));
+    }
+
+    // Makes matches ending in `index` report `index` itself again.
+    pub fn unalias(&mut self, index: usize) {
+        self.set_alias(index, None);
+    }
+
+    // Stops the states that stand in for `target` from accepting.
+    pub fn drop_aliases(&mut self, target: usize) {
+        for index in self.accepting_as(target).into_iter().skip(1) {
+            self.set_acceptance(index, false);
+            self.set_alias(index, None);
+        }
+    }
+
+    fn set_alias(&mut self, index: usize, alias: Option<usize>) {
+        let previous = mem::replace(&mut self.get_mut(index).alias, alias);
+        self.record(index, || Undo::Alias(index, previous));
+    }
+
+    fn push_next(&mut self, index: usize, next: usize) {
+        self.get_mut(index).next_states.push(next);
+        self.record(index, || Undo::NextState(index));
+    }
+
+    // Starts recording the changes made to the NFA, so that `rollback` can
+    // undo them without keeping a copy of every state.
+    pub fn checkpoint(&mut self) {
+        self.journal = Some(Journal {
+            len: self.states.len(),
+            undo: Vec::new(),
+        });
+    }
+
+    // Keeps the changes made since `checkpoint`.
+    pub fn commit(&mut self) {
+        self.journal = None;
+    }
+
+    // Undoes the changes made since `checkpoint`.
+    pub fn rollback(&mut self) {
+        let journal = match self.journal.take() {
+            Some(journal) => journal,
+            None => return,
+        };
+
+        for undo in journal.undo.into_iter().rev() {
+            match undo {
+                Undo::NextState(index) => {
+                    self.states[index].next_states.pop();
+                }
+                Undo::Acceptance(index, acceptance) => {
+                    self.states[index].acceptance = acceptance;
+                    self.acceptance[index] = acceptance;
+                }
+                Undo::StartCapture(index) => {
+                    self.states[index].start_capture = false;
+                    self.start_capture[index] = false;
+                }
+                Undo::EndCapture(index) => {
+                    self.states[index].end_capture = false;
+                    self.end_capture[index] = false;
+                }
+                Undo::Metadata(index, metadata) => self.states[index].metadata = metadata,
+                Undo::Alias(index, alias) => self.states[index].alias = alias,
+            }
+        }
+
+        self.states.truncate(journal.len);
+        self.acceptance.truncate(journal.len);
+        self.start_capture.truncate(journal.len);
+        self.end_capture.truncate(journal.len);
+    }
+
+    // Records how to undo a change to `index`, unless the state was added
+    // since the checkpoint.
+    fn record<F>(&mut self, index: usize, undo: F)
+    where
+        F: FnOnce() -> Undo<T>,
+    {
+        if let Some(ref mut journal) = self.journal {
+            if index < journal.len {
+                journal.undo.push(undo());
+            }
+        }
+    }
+
+    // The acceptance state that matches ending in `index` report.
+    pub fn canonical(&self, index: usize) -> usize {
+        self.get(index).alias.unwrap_or(index)
+    }
+
+    // The acceptance states that matches report as `target`, starting with
+    // `target` itself.
+    fn accepting_as(&self, target: usize) -> Vec<usize> {
+        let aliases = self
+            .states
+            .iter()
+            .filter(|state| state.alias == Some(target))
+            .map(|state| state.index);
+        Some(target).into_iter().chain(aliases).collect()
+    }
+
+    // Renders the NFA as a Graphviz DOT graph. Edges are labeled with the
+    // character class of the state they lead to, and `label` describes the
+    // metadata of acceptance states.
+    pub fn to_dot<F>(&self, mut label: F) -> String
+    where
+        F: FnMut(&T) -> String,
+    {
+        let mut dot = String::from("digraph nfa {\n    rankdir=LR;\n");
+
+        for state in &self.states {
+            let mut text = state.index.to_string();
+            if state.start_capture {
+                text.push_str("\nstart capture");
+            }
+            if state.end_capture {
+                text.push_str("\nend capture");
+            }
+            if let Some(ref metadata) = state.metadata {
+                text.push('\n');
+                text.push_str(&label(metadata));
+            }
+            if let Some(alias) = state.alias {
+                let _ = write!(text, "\nalias of {}", alias);
+            }
+
+            let shape = if state.acceptance {
+                "doublecircle"
+            } else {
+                "circle"
+            };
+            let _ = writeln!(
+                dot,
+                "    {} [shape={}, label=\"{}\"];",
+                state.index,
+                shape,
+                escape(&text)
+            );
+        }
+
+        for state in &self.states {
+            for &next in &state.next_states {
+                let chars = self.get(next).chars.to_string();
+                let _ = writeln!(
+                    dot,
+                    "    {} -> {} [label=\"{}\"];",
+                    state.index,
+                    next,
+                    escape(&chars)
+                );
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
     }
 
     fn new_state(&mut self, chars: CharacterClass) -> usize {
@@ -374,6 +874,277 @@ impl<T> NFA<T> {
     }
 }
 
+// Tables for exploring many pairs of routes of an NFA, computed once so that
+// exploring a pair only touches the states of those routes.
+#[derive(Debug)]
+pub struct Explorer<'a, T> {
+    nfa: &'a NFA<T>,
+    alphabet: Vec<char>,
+    previous: Vec<Vec<usize>>,
+    aliases: HashMap<usize, Vec<usize>>,
+}
+
+// The part of an NFA that can reach some acceptance states.
+#[derive(Debug)]
+pub struct Side {
+    targets: Vec<usize>,
+    accepting: HashSet<usize>,
+    next: HashMap<usize, Vec<usize>>,
+    // Whether a path reaching the side can start with each character of the
+    // alphabet.
+    first: Vec<bool>,
+    // The characters every path reaching the side starts with.
+    prefix: String,
+}
+
+impl<'a, T> Explorer<'a, T> {
+    pub fn new(nfa: &'a NFA<T>) -> Self {
+        let mut previous = vec![vec![]; nfa.states.len()];
+        let mut aliases: HashMap<usize, Vec<usize>> = HashMap::new();
+        for state in &nfa.states {
+            for &next in &state.next_states {
+                previous[next].push(state.index);
+            }
+            if let Some(alias) = state.alias {
+                aliases.entry(alias).or_default().push(state.index);
+            }
+        }
+
+        Self {
+            nfa,
+            alphabet: alphabet(nfa),
+            previous,
+            aliases,
+        }
+    }
+
+    // Restricts the NFA to the states from which one of `targets`, or a state
+    // that stands in for one of them, can be reached.
+    pub fn side(&self, targets: &[usize]) -> Side {
+        let mut accepting = HashSet::new();
+        for &target in targets {
+            accepting.insert(target);
+            if let Some(aliases) = self.aliases.get(&target) {
+                accepting.extend(aliases.iter().cloned());
+            }
+        }
+
+        let mut reaching = accepting.clone();
+        let mut queue: Vec<usize> = accepting.iter().cloned().collect();
+        while let Some(index) = queue.pop() {
+            for &previous in &self.previous[index] {
+                if reaching.insert(previous) {
+                    queue.push(previous);
+                }
+            }
+        }
+
+        // Follow the edges backwards, since states like the root have far
+        // more next states than previous ones.
+        let mut next: HashMap<usize, Vec<usize>> = HashMap::new();
+        for &index in &reaching {
+            next.entry(index).or_default();
+            for &previous in &self.previous[index] {
+                if reaching.contains(&previous) {
+                    next.entry(previous).or_default().push(index);
+                }
+            }
+        }
+
+        let start = next.get(&0).map_or(&[][..], |start| &start[..]);
+        let first = self
+            .alphabet
+            .iter()
+            .map(|&char| {
+                start
+                    .iter()
+                    .any(|&index| self.nfa.get(index).chars.matches(char))
+            })
+            .collect();
+
+        let mut prefix = String::new();
+        let mut current = 0;
+        while let Some(&[only]) = next.get(&current).map(|next| &next[..]) {
+            let char = match self.nfa.get(only).chars.single() {
+                Some(char) if !accepting.contains(&current) => char,
+                _ => break,
+            };
+            prefix.push(char);
+            current = only;
+        }
+
+        Side {
+            targets: targets.to_vec(),
+            accepting,
+            next,
+            first,
+            prefix,
+        }
+    }
+
+    // Returns `false` if no string reaches both sides, judging by the empty
+    // string, the first character and the prefix every string starts with.
+    pub fn may_overlap(&self, left: &Side, right: &Side) -> bool {
+        let empty = |side: &Side| side.accepting.contains(&0);
+        if !left.prefix.starts_with(&right.prefix) && !right.prefix.starts_with(&left.prefix) {
+            return false;
+        }
+        (empty(left) && empty(right))
+            || left
+                .first
+                .iter()
+                .zip(&right.first)
+                .any(|(&left, &right)| left && right)
+    }
+
+    // Explores the strings that reach `left`, shortest first, by running the
+    // NFA restricted to `left` alongside the NFA restricted to `right`.
+    // Strings that drive both sides into the same pair of state sets behave
+    // the same, so only one string per pair is visited. `visit` gets the
+    // string and the targets of `right` it reaches, and returns `false` to
+    // stop the search.
+    pub fn explore<F>(&self, left: &Side, right: &Side, visit: F)
+    where
+        F: FnMut(&str, &[usize]) -> bool,
+    {
+        self.search(left, right, false, visit);
+    }
+
+    // Finds the shortest string that reaches both `left` and `right`.
+    pub fn common(&self, left: &Side, right: &Side) -> Option<String> {
+        let mut common = None;
+        self.search(left, right, true, |string, accepted| {
+            if accepted.is_empty() {
+                return true;
+            }
+            common = Some(string.to_string());
+            false
+        });
+        common
+    }
+
+    // Like `explore`, but skips the strings that leave `right` behind if
+    // `both` is set.
+    fn search<F>(&self, left: &Side, right: &Side, both: bool, mut visit: F)
+    where
+        F: FnMut(&str, &[usize]) -> bool,
+    {
+        let start = |side: &Side| {
+            if side.next.contains_key(&0) {
+                vec![0]
+            } else {
+                vec![]
+            }
+        };
+        let step = |current: &[usize], side: &Side, char: char| {
+            let mut next: Vec<usize> = current
+                .iter()
+                .flat_map(|index| side.next[index].iter().cloned())
+                .filter(|&index| self.nfa.get(index).chars.matches(char))
+                .collect();
+            next.sort_unstable();
+            next.dedup();
+            next
+        };
+
+        let initial = (start(left), start(right));
+        if initial.0.is_empty() || both && initial.1.is_empty() {
+            return;
+        }
+
+        let mut seen = HashSet::new();
+        let mut queue = VecDeque::new();
+        seen.insert(initial.clone());
+        queue.push_back((String::new(), initial));
+
+        while let Some((string, (current_left, current_right))) = queue.pop_front() {
+            if current_left
+                .iter()
+                .any(|index| left.accepting.contains(index))
+            {
+                let accepted: Vec<usize> = right
+                    .targets
+                    .iter()
+                    .cloned()
+                    .filter(|&index| {
+                        current_right.iter().any(|&other| {
+                            right.accepting.contains(&other) && self.nfa.canonical(other) == index
+                        })
+                    })
+                    .collect();
+                if !visit(&string, &accepted) {
+                    return;
+                }
+            }
+
+            for &char in &self.alphabet {
+                let next_left = step(&current_left, left, char);
+                if next_left.is_empty() {
+                    continue;
+                }
+                let next = (next_left, step(&current_right, right, char));
+                if both && next.1.is_empty() {
+                    continue;
+                }
+                if seen.insert(next.clone()) {
+                    let mut string = string.clone();
+                    string.push(char);
+                    queue.push_back((string, next));
+                }
+            }
+        }
+    }
+}
+
+// Picks one character for every group of characters that all character
+// classes of the NFA treat the same, preferring readable ones.
+fn alphabet<T>(nfa: &NFA<T>) -> Vec<char> {
+    let mut classes: Vec<&CharacterClass> = vec![];
+    for state in &nfa.states {
+        if !classes.contains(&&state.chars) {
+            classes.push(&state.chars);
+        }
+    }
+
+    let mut non_ascii: Vec<char> = classes
+        .iter()
+        .flat_map(|chars| match chars {
+            ValidChars(set) | InvalidChars(set) => set.non_ascii.iter().cloned().collect(),
+            Ascii(..) => vec![],
+        })
+        .collect();
+    non_ascii.sort_unstable();
+    non_ascii.dedup();
+
+    let unlisted = ('\u{e000}'..='\u{f8ff}').find(|char| !non_ascii.contains(char));
+
+    let candidates = ('a'..='z')
+        .chain('0'..='9')
+        .chain('A'..='Z')
+        .chain((' '..='~').filter(|char| !char.is_ascii_alphanumeric()))
+        .chain(
+            (1..=128)
+                .filter_map(std::char::from_u32)
+                .filter(|char| char.is_ascii_control()),
+        )
+        .chain(non_ascii)
+        .chain(unlisted);
+
+    let mut signatures = HashSet::new();
+    candidates
+        .filter(|&char| {
+            let signature: Vec<bool> = classes.iter().map(|chars| chars.matches(char)).collect();
+            signatures.insert(signature)
+        })
+        .collect()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
 #[inline]
 fn fork_thread<T>(thread: &Thread, state: &State<T>) -> Thread {
     let mut new_trace = thread.clone();
@@ -389,11 +1160,13 @@ fn capture<T>(
     next_state: usize,
     pos: usize,
 ) {
-    if thread.capture_begin == None && nfa.start_capture[next_state] {
+    if thread.capture_begin.is_none() && nfa.start_capture[next_state] {
         thread.start_capture(pos);
     }
 
-    if thread.capture_begin != None && nfa.end_capture[current_state] && next_state > current_state
+    if thread.capture_begin.is_some()
+        && nfa.end_capture[current_state]
+        && next_state > current_state
     {
         thread.end_capture(pos);
     }
@@ -517,6 +1290,113 @@ mod tests {
         assert!(invalid.is_err(), "posts/ was invalid");
     }
 
+    #[test]
+    fn all_solutions() {
+        let mut nfa = NFA::<()>::new();
+        let a1 = nfa.put(0, CharacterClass::valid("n"));
+        let b1 = nfa.put(a1, CharacterClass::valid("e"));
+        let c1 = nfa.put(b1, CharacterClass::valid("w"));
+        nfa.acceptance(c1);
+
+        let a2 = nfa.put(0, CharacterClass::invalid("/"));
+        nfa.put_state(a2, a2);
+        nfa.acceptance(a2);
+
+        let all = nfa.process_all("new", |a| a);
+        let states: Vec<usize> = all.iter().map(|m| m.state).collect();
+        assert_eq!(states, vec![a2, c1]);
+
+        let all = nfa.process_all("old", |a| a);
+        let states: Vec<usize> = all.iter().map(|m| m.state).collect();
+        assert_eq!(states, vec![a2]);
+
+        assert!(nfa.process_all("a/b", |a| a).is_empty());
+    }
+
+    #[test]
+    fn prefix() {
+        let mut nfa = NFA::<()>::new();
+        let a = nfa.put(0, valid('a'));
+        let b = nfa.put(a, valid('/'));
+        let c = nfa.put(b, invalid('/'));
+        nfa.put_state(c, c);
+        nfa.start_capture(c);
+        nfa.end_capture(c);
+        nfa.acceptance(a);
+        nfa.acceptance(c);
+
+        let (m, len) = nfa
+            .process_prefix("a/bc/d", '/', |m| Some(m.state))
+            .unwrap();
+        assert_eq!((m.state, len), (c, 4));
+        assert_eq!(m.captures, vec!["bc"]);
+
+        let (m, len) = nfa
+            .process_prefix("a/bc/d", '/', |m| Some(m.state).filter(|&a| a != c))
+            .unwrap();
+        assert_eq!((m.state, len), (a, 1));
+
+        let (m, len) = nfa
+            .process_prefix("a/bc/d", '/', |m| (m.captures != ["bc"]).then_some(m.state))
+            .unwrap();
+        assert_eq!((m.state, len), (a, 1));
+
+        assert!(nfa.process_prefix("ab", '/', |m| Some(m.state)).is_none());
+    }
+
+    #[test]
+    fn trace() {
+        let mut nfa = NFA::<()>::new();
+        let a = nfa.put(0, valid('a'));
+        let b = nfa.put(a, valid('b'));
+        let c = nfa.put(a, invalid('/'));
+        nfa.put_state(c, c);
+        nfa.acceptance(b);
+        nfa.acceptance(c);
+
+        let trace = nfa.trace("ab", b);
+        assert_eq!((trace.consumed, trace.accepted), (2, true));
+
+        let trace = nfa.trace("ax", b);
+        assert_eq!((trace.consumed, trace.accepted), (1, false));
+        assert_eq!(trace.expected, vec![b]);
+
+        let trace = nfa.trace("a", c);
+        assert_eq!((trace.consumed, trace.accepted), (1, false));
+        assert_eq!(trace.expected, vec![c]);
+
+        let trace = nfa.trace("axyz", c);
+        assert_eq!((trace.consumed, trace.accepted), (4, true));
+    }
+
+    #[test]
+    fn explore() {
+        let mut nfa = NFA::<()>::new();
+        let a = nfa.put(0, valid('a'));
+        let b = nfa.put(a, valid('b'));
+        let c = nfa.put(a, invalid('/'));
+        nfa.put_state(c, c);
+        nfa.acceptance(b);
+        nfa.acceptance(c);
+
+        let mut visited = vec![];
+        nfa.explore(c, &[b], |string, accepted| {
+            visited.push((string.to_string(), accepted.to_vec()));
+            true
+        });
+        assert_eq!(
+            visited,
+            vec![("aa".to_string(), vec![]), ("ab".to_string(), vec![b])]
+        );
+
+        let mut visited = 0;
+        nfa.explore(c, &[b], |_, _| {
+            visited += 1;
+            false
+        });
+        assert_eq!(visited, 1);
+    }
+
     #[test]
     fn captures() {
         let mut nfa = NFA::<()>::new();
@@ -577,6 +1457,129 @@ mod tests {
         assert_eq!(post.unwrap().captures, vec!["123", "456"]);
     }
 
+    #[test]
+    fn display_character_class() {
+        assert_eq!(CharacterClass::any().to_string(), "any");
+        assert_eq!(valid('a').to_string(), "a");
+        assert_eq!(invalid('/').to_string(), "[^/]");
+        assert_eq!(CharacterClass::valid("ba").to_string(), "[ab]");
+        assert_eq!(CharacterClass::invalid("é.").to_string(), "[^.é]");
+        assert_eq!(valid('\n').to_string(), "\\n");
+    }
+
+    #[test]
+    fn put_tagged() {
+        let mut nfa = NFA::<()>::new();
+        let plain = nfa.put(0, CharacterClass::valid("a"));
+        let tagged = nfa.put_tagged(0, CharacterClass::valid("a"), Some("x"));
+
+        assert_ne!(plain, tagged);
+        assert_eq!(nfa.put(0, CharacterClass::valid("a")), plain);
+        assert_eq!(
+            nfa.put_tagged(0, CharacterClass::valid("a"), Some("x")),
+            tagged
+        );
+        assert_ne!(
+            nfa.put_tagged(0, CharacterClass::valid("a"), Some("y")),
+            tagged
+        );
+    }
+
+    #[test]
+    fn alias() {
+        let mut nfa = NFA::<()>::new();
+        let a = nfa.put(0, CharacterClass::valid("a"));
+        let b = nfa.put(0, CharacterClass::valid("b"));
+        nfa.acceptance(a);
+        nfa.alias(b, a);
+
+        assert_eq!(nfa.process("b", |i| i).unwrap().state, a);
+        let states: Vec<_> = nfa
+            .process_all("b", |i| i)
+            .iter()
+            .map(|m| m.state)
+            .collect();
+        assert_eq!(states, vec![a]);
+        assert!(nfa.trace("b", a).accepted);
+
+        let mut examples = vec![];
+        nfa.explore(a, &[a], |path, accepted| {
+            examples.push((path.to_string(), accepted.to_vec()));
+            true
+        });
+        assert_eq!(
+            examples,
+            vec![("a".to_string(), vec![a]), ("b".to_string(), vec![a])]
+        );
+    }
+
+    #[test]
+    fn put_chain() {
+        let mut nfa = NFA::<()>::new();
+        let classes = [
+            CharacterClass::valid("a"),
+            CharacterClass::valid("a"),
+            CharacterClass::valid("b"),
+        ];
+        let chain = nfa.put_chain(0, &classes, "x");
+        assert_eq!(chain.len(), 3);
+        assert!(chain[0] > chain[1] && chain[1] > chain[2]);
+        assert_eq!(nfa.put_chain(0, &classes, "x"), chain);
+        assert_ne!(nfa.put_chain(0, &classes[..2], "y")[0], chain[0]);
+
+        nfa.acceptance(chain[2]);
+        assert!(nfa.process("aab", |i| i).is_ok());
+        assert!(nfa.process("aa", |i| i).is_err());
+    }
+
+    #[test]
+    fn rollback() {
+        let mut nfa = NFA::<&str>::new();
+        let a = nfa.put(0, valid('a'));
+        nfa.acceptance(a);
+        nfa.metadata(a, "a");
+
+        nfa.checkpoint();
+        let b = nfa.put(a, valid('b'));
+        nfa.acceptance(b);
+        nfa.metadata(a, "changed");
+        nfa.alias(0, a);
+        nfa.rollback();
+
+        assert!(nfa.process("ab", |i| i).is_err());
+        assert!(nfa.process("", |i| i).is_err());
+        assert_eq!(nfa.get(a).metadata, Some("a"));
+        assert_eq!(nfa.get(a).next_states, Vec::<usize>::new());
+
+        nfa.checkpoint();
+        let b = nfa.put(a, valid('b'));
+        nfa.acceptance(b);
+        nfa.commit();
+        nfa.rollback();
+        assert_eq!(nfa.process("ab", |i| i).unwrap().state, b);
+    }
+
+    #[test]
+    fn to_dot() {
+        let mut nfa = NFA::<&str>::new();
+        let a = nfa.put(0, valid('a'));
+        let b = nfa.put(a, invalid('/'));
+        nfa.put_state(b, b);
+        nfa.start_capture(b);
+        nfa.end_capture(b);
+        nfa.acceptance(b);
+        nfa.metadata(b, "a \"route\"");
+
+        let dot = nfa.to_dot(|m| m.to_string());
+        assert!(dot.starts_with("digraph nfa {"));
+        assert!(dot.contains("    0 [shape=circle, label=\"0\"];"));
+        assert!(dot.contains(
+            "    2 [shape=doublecircle, label=\"2\\nstart capture\\nend capture\\na \\\"route\\\"\"];"
+        ));
+        assert!(dot.contains("    0 -> 1 [label=\"a\"];"));
+        assert!(dot.contains("    2 -> 2 [label=\"[^/]\"];"));
+    }
+
     #[test]
     fn test_ascii_set() {
         let mut set = CharSet::new();
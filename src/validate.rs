@@ -0,0 +1,193 @@
+use std::fmt;
+use std::sync::Arc;
+
+use crate::{nfa, Router};
+
+/// Checks the value captured by a param, registered with
+/// `Router::add_validator`.
+///
+/// Closures that take a `&str` and return a `bool` implement this trait.
+pub trait Validator: Send + Sync {
+    /// Returns `true` if `value` is valid.
+    fn validate(&self, value: &str) -> bool;
+}
+
+impl<F> Validator for F
+where
+    F: Fn(&str) -> bool + Send + Sync,
+{
+    fn validate(&self, value: &str) -> bool {
+        self(value)
+    }
+}
+
+/// A `Validator` shared between clones of a router.
+#[derive(Clone)]
+pub(crate) struct Shared(Arc<dyn Validator>);
+
+impl fmt::Debug for Shared {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Shared").finish()
+    }
+}
+
+impl<T> Router<T> {
+    /// Register a validator that patterns can refer to as `:param<name>`.
+    ///
+    /// A route only matches if every validator it refers to accepts the value
+    /// its param captured. Otherwise lower-ranked routes get the chance to
+    /// match. A route that refers to a validator that was never registered
+    /// does not match at all. Validators are not serialized.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use route_recognizer::Router;
+    ///
+    /// let mut router = Router::new();
+    /// router.add_validator("int", |value: &str| value.parse::<u64>().is_ok());
+    /// router.add("/posts/:id<int>", "post");
+    /// router.add("/posts/:slug", "slug");
+    ///
+    /// let m = router.recognize("/posts/12").unwrap();
+    /// assert_eq!(*m.handler(), &"post");
+    /// assert_eq!(m.params().find("id"), Some("12"));
+    ///
+    /// let m = router.recognize("/posts/hello").unwrap();
+    /// assert_eq!(*m.handler(), &"slug");
+    /// ```
+    pub fn add_validator<V: Validator + 'static>(&mut self, name: &str, validator: V) {
+        self.validators
+            .insert(name.to_string(), Shared(Arc::new(validator)));
+    }
+
+    /// Find the first param of a match whose validator rejects its value,
+    /// along with the name of that validator.
+    pub(crate) fn rejection<'a>(
+        &'a self,
+        nfa_match: &nfa::Match<'_>,
+    ) -> Option<(&'a str, &'a str)> {
+        let metadata = self.metadata(nfa_match.state);
+        let validators = metadata.param_names.iter().zip(&metadata.validators);
+
+        for ((name, validator), capture) in validators.zip(&nfa_match.captures) {
+            let validator = match validator {
+                Some(validator) => validator,
+                None => continue,
+            };
+            let valid = match self.validators.get(validator) {
+                Some(Shared(check)) => check.validate(capture),
+                None => false,
+            };
+            if !valid {
+                return Some((name, validator));
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Router;
+
+    fn router() -> Router<&'static str> {
+        let mut router = Router::new();
+        router.add_validator("int", |value: &str| value.parse::<u64>().is_ok());
+        router.add_validator("hex", |value: &str| {
+            value.bytes().all(|b| b.is_ascii_hexdigit())
+        });
+        router.add("/posts/:id<int>", "int");
+        router.add("/posts/:id<hex>", "hex");
+        router.add("/posts/:slug", "slug");
+        router.add("/files/*path<missing>", "missing");
+        router
+    }
+
+    #[test]
+    fn validators() {
+        let router = router();
+
+        assert_eq!(*router.recognize("/posts/12").unwrap().handler(), &"int");
+        assert_eq!(*router.recognize("/posts/ff").unwrap().handler(), &"hex");
+
+        let m = router.recognize("/posts/hello").unwrap();
+        assert_eq!(*m.handler(), &"slug");
+        assert_eq!(m.params().find("slug"), Some("hello"));
+
+        let handlers: Vec<_> = router
+            .recognize_all("/posts/12")
+            .iter()
+            .map(|m| **m.handler())
+            .collect();
+        assert_eq!(handlers, vec!["int", "hex", "slug"]);
+
+        assert!(router.recognize("/files/a").is_err());
+        let route = router.routes().find(|r| *r.handler() == "int").unwrap();
+        assert_eq!(route.param_names(), vec!["id"]);
+    }
+
+    #[test]
+    fn prefix_validators() {
+        let mut router = Router::new();
+        router.add_validator("int", |value: &str| value.parse::<u64>().is_ok());
+        router.add("/a/:id<int>", "int");
+        router.add("/a/:slug", "slug");
+        router.add("/b/:id<int>", "b");
+        router.add("/b", "root");
+
+        let m = router.recognize_prefix("/a/xx/rest").unwrap();
+        assert_eq!(*m.handler(), &"slug");
+        assert_eq!(m.rest(), "/rest");
+
+        let m = router.recognize_prefix("/a/12/rest").unwrap();
+        assert_eq!(*m.handler(), &"int");
+
+        // A shorter prefix is tried if every route of the longest one rejects.
+        let m = router.recognize_prefix("/b/xx/rest").unwrap();
+        assert_eq!(*m.handler(), &"root");
+        assert_eq!(m.rest(), "/xx/rest");
+    }
+
+    #[test]
+    fn mounted_validators() {
+        let api = || {
+            let mut api = Router::new();
+            api.add_validator("int", |value: &str| value.parse::<u64>().is_ok());
+            api.add("/:id<int>", "int");
+            api
+        };
+
+        let mut router = Router::new();
+        router.mount("/api", api());
+        let m = router.recognize("/api/12").unwrap();
+        assert_eq!(*m.handler(), &"int");
+        assert_eq!(m.params().find("id"), Some("12"));
+        assert!(router.recognize("/api/x").is_err());
+
+        // The validators of the parent router win.
+        let mut router = Router::new();
+        router.add_validator("int", |value: &str| value == "0");
+        router.mount("/api", api());
+        assert!(router.recognize("/api/0").is_ok());
+        assert!(router.recognize("/api/12").is_err());
+    }
+
+    #[test]
+    fn validators_lint() {
+        let router = router();
+        assert!(router.lint().is_empty());
+
+        let explanation = router.explain("/posts/hello");
+        assert_eq!(explanation.winner().unwrap().pattern(), "/posts/:slug");
+        assert_eq!(
+            explanation.to_string(),
+            "path: /posts/hello\n\
+             \x20 1. /posts/:slug matched (statics: 1, dynamics: 1, wildcards: 0)\n\
+             \x20 -  /files/*path<missing> failed at offset 1, expected f\n\
+             \x20 -  /posts/:id<hex> matched, but validator hex rejected id\n\
+             \x20 -  /posts/:id<int> matched, but validator int rejected id\n"
+        );
+    }
+}